@@ -0,0 +1,189 @@
+//! Timed exp events that multiply the experience points for messages.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serenity::model::prelude::ChannelId;
+
+use crate::db::dao;
+
+/// Returns the current time as the number of seconds since the Unix epoch.
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX)
+        })
+}
+
+/// An exp event that hasn't ended yet.
+///
+/// The times are the numbers of seconds since the Unix epoch.
+#[derive(Debug, Clone)]
+pub(crate) struct ExpEvent {
+    pub(crate) id: i64,
+    pub(crate) multiplier: f64,
+    pub(crate) starts_at: i64,
+    pub(crate) ends_at: i64,
+    /// The channels where the event applies. The event is global if there are none.
+    pub(crate) channels: Vec<ChannelId>,
+    pub(crate) start_announced: bool,
+}
+
+/// A change of an exp event that should be announced.
+#[derive(Debug, Clone)]
+pub(crate) enum EventAnnouncement {
+    Started(ExpEvent),
+    Ended(ExpEvent),
+}
+
+/// The exp events that haven't ended yet, ordered by their start.
+#[derive(Debug)]
+pub(crate) struct ExpEvents(Vec<ExpEvent>);
+
+impl ExpEvent {
+    pub(crate) fn is_active(&self, now: i64) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+
+    fn applies_in(&self, channel_id: ChannelId) -> bool {
+        self.channels.is_empty() || self.channels.contains(&channel_id)
+    }
+
+    /// Describes where the event applies, for example ` in #general, #memes`.
+    /// Global events are described with an empty string.
+    pub(crate) fn scope(&self) -> String {
+        if self.channels.is_empty() {
+            return String::new();
+        }
+        let mentions: Vec<String> = self
+            .channels
+            .iter()
+            .map(|channel_id| format!("<#{channel_id}>"))
+            .collect();
+        format!(" in {}", mentions.join(", "))
+    }
+}
+
+impl EventAnnouncement {
+    /// Returns the message that announces the change of the event.
+    pub(crate) fn content(&self) -> String {
+        match self {
+            EventAnnouncement::Started(
+                event @ ExpEvent {
+                    multiplier,
+                    ends_at,
+                    ..
+                },
+            ) => format!(
+                "A {multiplier}x exp event has started{}! It ends <t:{ends_at}:R>.",
+                event.scope()
+            ),
+            EventAnnouncement::Ended(event @ ExpEvent { multiplier, .. }) => {
+                format!("The {multiplier}x exp event{} has ended.", event.scope())
+            }
+        }
+    }
+}
+
+impl From<dao::ExpEvent> for ExpEvent {
+    fn from(dao: dao::ExpEvent) -> Self {
+        let dao::ExpEvent {
+            id,
+            multiplier,
+            starts_at,
+            ends_at,
+            channel_ids,
+            start_announced,
+        } = dao;
+        #[allow(clippy::cast_sign_loss)]
+        let channels: Vec<ChannelId> = channel_ids
+            .into_iter()
+            .map(|channel_id| ChannelId(channel_id as u64))
+            .collect();
+        Self {
+            id,
+            multiplier,
+            starts_at,
+            ends_at,
+            channels,
+            start_announced,
+        }
+    }
+}
+
+impl ExpEvents {
+    pub(crate) fn new(events: Vec<dao::ExpEvent>) -> Self {
+        let mut events: Vec<ExpEvent> = events.into_iter().map(ExpEvent::from).collect();
+        events.sort_by_key(|event| event.starts_at);
+        Self(events)
+    }
+
+    /// Returns the upcoming and the active events, ordered by their start.
+    pub(crate) fn events(&self) -> &[ExpEvent] {
+        let Self(events) = self;
+        events
+    }
+
+    /// Returns the multiplier of the events active in the channel.
+    ///
+    /// Overlapping events don't stack, the highest multiplier wins.
+    pub(crate) fn multiplier(&self, channel_id: ChannelId, now: i64) -> f64 {
+        let Self(events) = self;
+        events
+            .iter()
+            .filter(|event| event.is_active(now) && event.applies_in(channel_id))
+            .map(|event| event.multiplier)
+            .fold(1.0, f64::max)
+    }
+
+    /// Applies the multiplier of the events active in the channel to the amount of exp.
+    pub(crate) fn apply(&self, base: i64, channel_id: ChannelId, now: i64) -> i64 {
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        let delta = (base as f64 * self.multiplier(channel_id, now)).round() as i64;
+        delta
+    }
+
+    pub(super) fn insert(&mut self, event: ExpEvent) {
+        let Self(events) = self;
+        let idx = events.partition_point(|other| other.starts_at <= event.starts_at);
+        events.insert(idx, event);
+    }
+
+    pub(super) fn remove(&mut self, id: i64) -> Option<ExpEvent> {
+        let Self(events) = self;
+        let idx = events.iter().position(|event| event.id == id)?;
+        Some(events.remove(idx))
+    }
+
+    /// Returns the starts and the ends of the events that are due to be announced.
+    ///
+    /// The events are not changed, see [`ExpEvents::mark_announced`].
+    pub(super) fn due_announcements(&self, now: i64) -> Vec<EventAnnouncement> {
+        let Self(events) = self;
+        let mut announcements = Vec::new();
+        for event in events {
+            if event.starts_at <= now && !event.start_announced {
+                announcements.push(EventAnnouncement::Started(event.clone()));
+            }
+            if event.ends_at <= now {
+                announcements.push(EventAnnouncement::Ended(event.clone()));
+            }
+        }
+        announcements
+    }
+
+    /// Records that the announcement is taken care of. Ended events are forgotten.
+    pub(super) fn mark_announced(&mut self, announcement: &EventAnnouncement) {
+        match announcement {
+            EventAnnouncement::Started(started) => {
+                let Self(events) = self;
+                if let Some(event) = events.iter_mut().find(|event| event.id == started.id) {
+                    event.start_announced = true;
+                }
+            }
+            EventAnnouncement::Ended(ended) => {
+                self.remove(ended.id);
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
 use core::convert::identity as id;
+use std::str::FromStr;
+
+use serenity::model::prelude::{ChannelId, UserId};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct Exp(pub(crate) u64);
@@ -11,10 +14,78 @@ impl Exp {
         exp
     }
 
+    /// Converts the signed amount of exp, clamping negative amounts to 0
+    /// since exp never goes negative.
     pub(crate) fn from_i64(exp: i64) -> Self {
-        debug_assert!(exp >= 0);
-        #[allow(clippy::cast_sign_loss)]
-        let exp: u64 = id::<i64>(exp) as u64;
-        Exp(exp)
+        Exp(u64::try_from(exp).unwrap_or(0))
+    }
+}
+
+/// The activity that changed the exp of a member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExpSource {
+    Message,
+    Voice,
+    /// An adjustment made by a moderator.
+    Admin,
+    /// A bonus granted by an exp event.
+    Event,
+    /// The decay of the exp of an inactive member.
+    Decay,
+    /// The reset of the exp at the end of a season.
+    Season,
+    /// A bonus for a milestone of a daily activity streak.
+    Streak,
+}
+
+/// A change of the exp of a member, as recorded in the exp ledger.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExpChange {
+    pub(crate) delta: i64,
+    pub(crate) source: ExpSource,
+    /// The channel of the activity, if there was one.
+    pub(crate) channel_id: Option<ChannelId>,
+}
+
+impl ExpSource {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            ExpSource::Message => "message",
+            ExpSource::Voice => "voice",
+            ExpSource::Admin => "admin",
+            ExpSource::Event => "event",
+            ExpSource::Decay => "decay",
+            ExpSource::Season => "season",
+            ExpSource::Streak => "streak",
+        }
     }
+
+    /// Whether the exp from this source shows that the member is active.
+    pub(crate) fn is_activity(self) -> bool {
+        matches!(self, ExpSource::Message | ExpSource::Voice)
+    }
+}
+
+impl FromStr for ExpSource {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "message" => Ok(ExpSource::Message),
+            "voice" => Ok(ExpSource::Voice),
+            "admin" => Ok(ExpSource::Admin),
+            "event" => Ok(ExpSource::Event),
+            "decay" => Ok(ExpSource::Decay),
+            "season" => Ok(ExpSource::Season),
+            "streak" => Ok(ExpSource::Streak),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A change of the exp made by a moderator, as recorded in the audit trail.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExpAdjustment<'a> {
+    pub(crate) actor_id: UserId,
+    pub(crate) reason: Option<&'a str>,
 }
@@ -0,0 +1,423 @@
+//! Rules that decide which messages are rewarded with experience points.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use serenity::model::prelude::{ChannelId, RoleId, UserId};
+
+use super::exp::Exp;
+use crate::{
+    db::dao,
+    immut_data::{consts::MAX_EXP_MULTIPLIER, dynamic::WHITESPACE},
+};
+
+/// Runtime-configurable settings of the experience system.
+///
+/// The settings are stored in the single row of the `exp_settings` table.
+#[derive(Debug, Clone)]
+pub(crate) struct ExpSettings {
+    /// The minimum time between two rewarded messages of a member.
+    pub(crate) msg_cooldown: Duration,
+    /// The minimum number of letters and digits in a rewarded message.
+    pub(crate) min_msg_chars: usize,
+    /// Whether a message repeating the previous message of the member is not rewarded.
+    pub(crate) ignore_duplicate_msgs: bool,
+    /// The experience points for a minute of talking in a voice channel.
+    pub(crate) voice_exp_per_min: i64,
+    /// The number of days without activity after which the exp starts to decay.
+    /// 0 turns the decay off.
+    pub(crate) decay_after_days: u32,
+    /// The percentage of the exp that decays every decay period.
+    pub(crate) decay_percent: u32,
+    /// The number of days between two decays of the exp of an inactive member.
+    pub(crate) decay_period_days: u32,
+    /// The exp never decays below this amount.
+    pub(crate) decay_floor: u64,
+}
+
+/// A change of one of the [`ExpSettings`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ExpSetting {
+    MsgCooldown(Duration),
+    MinMsgChars(usize),
+    IgnoreDuplicateMsgs(bool),
+    VoiceExpPerMin(i64),
+    DecayAfterDays(u32),
+    DecayPercent(u32),
+    DecayPeriodDays(u32),
+    DecayFloor(u64),
+}
+
+/// Multipliers of the experience points awarded for messages.
+///
+/// The multiplier of the channel and the multipliers of all roles of the author
+/// are multiplied together. Channels and roles without a multiplier count as 1.
+#[derive(Debug, Default)]
+pub(crate) struct ExpMultipliers {
+    pub(crate) channels: HashMap<ChannelId, f64>,
+    pub(crate) roles: HashMap<RoleId, f64>,
+}
+
+/// The last message of a member that is remembered for the anti-farming checks.
+#[derive(Debug)]
+struct LastMsg {
+    rewarded_at: Option<Instant>,
+    normalized_content: String,
+}
+
+/// The recent message activity of the members.
+///
+/// It is kept only in memory, so the cooldowns are reset when Vampy restarts.
+#[derive(Debug, Default)]
+pub(crate) struct MsgActivity(HashMap<UserId, LastMsg>);
+
+impl ExpSetting {
+    pub(crate) const NAMES: &'static [&'static str] = &[
+        "cooldown",
+        "min-chars",
+        "ignore-duplicates",
+        "voice-exp",
+        "decay-after",
+        "decay-percent",
+        "decay-period",
+        "decay-floor",
+    ];
+
+    /// The longest cooldown, a day.
+    const MAX_MSG_COOLDOWN_SECS: u64 = 24 * 60 * 60;
+    /// The highest minimum, the length limit of Discord messages.
+    const MAX_MIN_MSG_CHARS: usize = 2000;
+
+    /// Parses the setting from its name and the value given by a command.
+    pub(crate) fn parse(name: &str, value: &str) -> Result<Self, String> {
+        match name {
+            "cooldown" => match value.parse::<u64>() {
+                Ok(secs) if secs <= Self::MAX_MSG_COOLDOWN_SECS => {
+                    Ok(ExpSetting::MsgCooldown(Duration::from_secs(secs)))
+                }
+                _ => Err(format!(
+                    "The cooldown must be a whole number of seconds, at most {}.",
+                    Self::MAX_MSG_COOLDOWN_SECS
+                )),
+            },
+            "min-chars" => match value.parse::<usize>() {
+                Ok(min_msg_chars) if min_msg_chars <= Self::MAX_MIN_MSG_CHARS => {
+                    Ok(ExpSetting::MinMsgChars(min_msg_chars))
+                }
+                _ => Err(format!(
+                    "The minimum number of characters must be a whole number, at most {}.",
+                    Self::MAX_MIN_MSG_CHARS
+                )),
+            },
+            "ignore-duplicates" => match value {
+                "on" | "true" | "yes" => Ok(ExpSetting::IgnoreDuplicateMsgs(true)),
+                "off" | "false" | "no" => Ok(ExpSetting::IgnoreDuplicateMsgs(false)),
+                _ => Err("The value must be either `on` or `off`.".to_string()),
+            },
+            "voice-exp" => match value.parse::<i64>() {
+                Ok(exp) if exp >= 0 => Ok(ExpSetting::VoiceExpPerMin(exp)),
+                _ => Err("The exp per minute must be a non-negative whole number.".to_string()),
+            },
+            "decay-after" => value
+                .parse::<u32>()
+                .map(ExpSetting::DecayAfterDays)
+                .map_err(|_| "The number of days must be a whole number.".to_string()),
+            "decay-percent" => match value.trim_end_matches('%').parse::<u32>() {
+                Ok(percent) if (1..=100).contains(&percent) => {
+                    Ok(ExpSetting::DecayPercent(percent))
+                }
+                _ => Err("The percentage must be a whole number from 1 to 100.".to_string()),
+            },
+            "decay-period" => match value.parse::<u32>() {
+                Ok(days) if days >= 1 => Ok(ExpSetting::DecayPeriodDays(days)),
+                _ => Err("The decay period must be a positive whole number of days.".to_string()),
+            },
+            "decay-floor" => value
+                .parse::<u64>()
+                .map(ExpSetting::DecayFloor)
+                .map_err(|_| "The floor must be a non-negative whole number.".to_string()),
+            _ => Err(format!(
+                "Unknown setting. Try one of: `{}`.",
+                Self::NAMES.join("`, `")
+            )),
+        }
+    }
+}
+
+impl Display for ExpSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpSetting::MsgCooldown(cooldown) => {
+                write!(f, "`cooldown`: {} s", cooldown.as_secs())
+            }
+            ExpSetting::MinMsgChars(min_msg_chars) => {
+                write!(f, "`min-chars`: {min_msg_chars}")
+            }
+            ExpSetting::IgnoreDuplicateMsgs(ignore) => {
+                let state = if *ignore { "on" } else { "off" };
+                write!(f, "`ignore-duplicates`: {state}")
+            }
+            ExpSetting::VoiceExpPerMin(exp) => {
+                write!(f, "`voice-exp`: {exp} per minute")
+            }
+            ExpSetting::DecayAfterDays(0) => write!(f, "`decay-after`: off"),
+            ExpSetting::DecayAfterDays(days) => {
+                write!(f, "`decay-after`: {days} days without activity")
+            }
+            ExpSetting::DecayPercent(percent) => write!(f, "`decay-percent`: {percent}%"),
+            ExpSetting::DecayPeriodDays(days) => write!(f, "`decay-period`: every {days} days"),
+            ExpSetting::DecayFloor(floor) => write!(f, "`decay-floor`: {floor} exp"),
+        }
+    }
+}
+
+impl ExpSettings {
+    pub(crate) fn apply(&mut self, setting: ExpSetting) {
+        match setting {
+            ExpSetting::MsgCooldown(cooldown) => self.msg_cooldown = cooldown,
+            ExpSetting::MinMsgChars(min_msg_chars) => self.min_msg_chars = min_msg_chars,
+            ExpSetting::IgnoreDuplicateMsgs(ignore) => self.ignore_duplicate_msgs = ignore,
+            ExpSetting::VoiceExpPerMin(exp) => self.voice_exp_per_min = exp,
+            ExpSetting::DecayAfterDays(days) => self.decay_after_days = days,
+            ExpSetting::DecayPercent(percent) => self.decay_percent = percent,
+            ExpSetting::DecayPeriodDays(days) => self.decay_period_days = days,
+            ExpSetting::DecayFloor(floor) => self.decay_floor = floor,
+        }
+    }
+
+    /// Returns the current values of all settings.
+    pub(crate) fn to_settings(&self) -> Vec<ExpSetting> {
+        vec![
+            ExpSetting::MsgCooldown(self.msg_cooldown),
+            ExpSetting::MinMsgChars(self.min_msg_chars),
+            ExpSetting::IgnoreDuplicateMsgs(self.ignore_duplicate_msgs),
+            ExpSetting::VoiceExpPerMin(self.voice_exp_per_min),
+            ExpSetting::DecayAfterDays(self.decay_after_days),
+            ExpSetting::DecayPercent(self.decay_percent),
+            ExpSetting::DecayPeriodDays(self.decay_period_days),
+            ExpSetting::DecayFloor(self.decay_floor),
+        ]
+    }
+
+    /// Returns the amount of exp that decays from `exp` in one decay period.
+    ///
+    /// The amount is rounded up, so that small amounts of exp decay too,
+    /// but it never takes the exp below the floor.
+    pub(crate) fn decay(&self, exp: Exp) -> Exp {
+        let Exp(exp) = exp;
+        // Widened so that the product can't overflow; the result is at most `exp` again
+        let decayed: u128 = (u128::from(exp) * u128::from(self.decay_percent)).div_ceil(100);
+        let decayed: u64 = u64::try_from(decayed).unwrap_or(u64::MAX);
+        Exp(decayed.min(exp.saturating_sub(self.decay_floor)))
+    }
+}
+
+impl From<dao::ExpSettings> for ExpSettings {
+    fn from(dao: dao::ExpSettings) -> Self {
+        let dao::ExpSettings {
+            msg_cooldown_secs,
+            min_msg_chars,
+            ignore_duplicate_msgs,
+            voice_exp_per_min,
+            decay_after_days,
+            decay_percent,
+            decay_period_days,
+            decay_floor,
+        } = dao;
+        // The database constraints guarantee that the values are non-negative
+        #[allow(clippy::cast_sign_loss)]
+        let msg_cooldown = Duration::from_secs(msg_cooldown_secs as u64);
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let min_msg_chars = min_msg_chars as usize;
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let (decay_after_days, decay_percent, decay_period_days) = (
+            decay_after_days as u32,
+            decay_percent as u32,
+            decay_period_days as u32,
+        );
+        #[allow(clippy::cast_sign_loss)]
+        let decay_floor = decay_floor as u64;
+        Self {
+            msg_cooldown,
+            min_msg_chars,
+            ignore_duplicate_msgs,
+            voice_exp_per_min,
+            decay_after_days,
+            decay_percent,
+            decay_period_days,
+            decay_floor,
+        }
+    }
+}
+
+impl ExpMultipliers {
+    pub(crate) fn new(
+        channel_multipliers: Vec<dao::ChannelExpMultiplier>,
+        role_multipliers: Vec<dao::RoleExpMultiplier>,
+    ) -> Self {
+        #[allow(clippy::cast_sign_loss)]
+        let channels = channel_multipliers
+            .into_iter()
+            .map(|m| (ChannelId(m.channel_id as u64), m.multiplier))
+            .collect();
+        #[allow(clippy::cast_sign_loss)]
+        let roles = role_multipliers
+            .into_iter()
+            .map(|m| (RoleId(m.role_id as u64), m.multiplier))
+            .collect();
+        Self { channels, roles }
+    }
+
+    pub(crate) fn channel(&self, channel_id: ChannelId) -> f64 {
+        self.channels.get(&channel_id).copied().unwrap_or(1.0)
+    }
+
+    /// Applies the multipliers of the channel and of the roles to the base amount of exp.
+    ///
+    /// The combined multiplier is capped at [`MAX_EXP_MULTIPLIER`].
+    pub(crate) fn apply(&self, base: i64, channel_id: ChannelId, role_ids: &[RoleId]) -> i64 {
+        let multiplier: f64 = (role_ids
+            .iter()
+            .filter_map(|role_id| self.roles.get(role_id))
+            .product::<f64>()
+            * self.channel(channel_id))
+        .min(MAX_EXP_MULTIPLIER);
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        let delta = (base as f64 * multiplier).round() as i64;
+        delta
+    }
+}
+
+impl MsgActivity {
+    /// Records the message of the member and checks whether it deserves experience points.
+    ///
+    /// A message is not rewarded if it is too short, if it repeats the previous message
+    /// of the member, or if the previous rewarded message was sent too recently.
+    pub(crate) fn record_msg(
+        &mut self,
+        settings: &ExpSettings,
+        user_id: UserId,
+        content: &str,
+    ) -> bool {
+        let Self(last_msgs) = self;
+        let now = Instant::now();
+        let normalized_content: String = WHITESPACE.replace_all(content.trim(), " ").to_lowercase();
+        let meaningful_chars = normalized_content
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .count();
+
+        let last_msg = last_msgs.entry(user_id).or_insert_with(|| LastMsg {
+            rewarded_at: None,
+            normalized_content: String::new(),
+        });
+        let is_duplicate = settings.ignore_duplicate_msgs
+            && !last_msg.normalized_content.is_empty()
+            && last_msg.normalized_content == normalized_content;
+        let is_on_cooldown = match last_msg.rewarded_at {
+            Some(rewarded_at) => now.duration_since(rewarded_at) < settings.msg_cooldown,
+            None => false,
+        };
+        let is_rewarded =
+            !is_duplicate && !is_on_cooldown && meaningful_chars >= settings.min_msg_chars;
+
+        last_msg.normalized_content = normalized_content;
+        if is_rewarded {
+            last_msg.rewarded_at = Some(now);
+        }
+        is_rewarded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(
+        cooldown_secs: u64,
+        min_msg_chars: usize,
+        ignore_duplicate_msgs: bool,
+    ) -> ExpSettings {
+        ExpSettings {
+            msg_cooldown: Duration::from_secs(cooldown_secs),
+            min_msg_chars,
+            ignore_duplicate_msgs,
+            voice_exp_per_min: 0,
+            decay_after_days: 0,
+            decay_percent: 5,
+            decay_period_days: 7,
+            decay_floor: 0,
+        }
+    }
+
+    #[test]
+    fn cooldown_blocks_the_next_message() {
+        let settings = settings(60, 0, false);
+        let mut activity = MsgActivity::default();
+        assert!(activity.record_msg(&settings, UserId(1), "first"));
+        assert!(!activity.record_msg(&settings, UserId(1), "second"));
+        // The cooldowns of the members are independent
+        assert!(activity.record_msg(&settings, UserId(2), "third"));
+    }
+
+    #[test]
+    fn no_cooldown_rewards_every_message() {
+        let settings = settings(0, 0, false);
+        let mut activity = MsgActivity::default();
+        assert!(activity.record_msg(&settings, UserId(1), "first"));
+        assert!(activity.record_msg(&settings, UserId(1), "second"));
+    }
+
+    #[test]
+    fn short_messages_are_not_rewarded() {
+        let settings = settings(60, 5, false);
+        let mut activity = MsgActivity::default();
+        // Only letters and digits count
+        assert!(!activity.record_msg(&settings, UserId(1), "a . b . c . d"));
+        // The short message didn't start the cooldown
+        assert!(activity.record_msg(&settings, UserId(1), "hello"));
+    }
+
+    #[test]
+    fn duplicates_are_not_rewarded() {
+        let settings = settings(0, 0, true);
+        let mut activity = MsgActivity::default();
+        assert!(activity.record_msg(&settings, UserId(1), "Hello  there"));
+        // Whitespace and case are ignored
+        assert!(!activity.record_msg(&settings, UserId(1), " hello there "));
+        assert!(activity.record_msg(&settings, UserId(1), "general kenobi"));
+    }
+
+    #[test]
+    fn duplicates_are_rewarded_when_allowed() {
+        let settings = settings(0, 0, false);
+        let mut activity = MsgActivity::default();
+        assert!(activity.record_msg(&settings, UserId(1), "hello"));
+        assert!(activity.record_msg(&settings, UserId(1), "hello"));
+    }
+
+    #[test]
+    fn settings_are_bounded() {
+        assert!(ExpSetting::parse("cooldown", "86400").is_ok());
+        assert!(ExpSetting::parse("cooldown", "86401").is_err());
+        assert!(ExpSetting::parse("cooldown", "18446744073709551615").is_err());
+        assert!(ExpSetting::parse("min-chars", "2000").is_ok());
+        assert!(ExpSetting::parse("min-chars", "2001").is_err());
+    }
+    #[test]
+    fn combined_multiplier_is_capped() {
+        let multipliers = ExpMultipliers {
+            channels: HashMap::from([(ChannelId(1), 5.0)]),
+            roles: HashMap::from([(RoleId(2), 4.0), (RoleId(3), 0.5)]),
+        };
+        assert_eq!(multipliers.apply(5, ChannelId(1), &[RoleId(3)]), 13);
+        assert_eq!(multipliers.apply(5, ChannelId(1), &[RoleId(2)]), 50);
+        assert_eq!(
+            multipliers.apply(5, ChannelId(2), &[RoleId(2), RoleId(4)]),
+            20
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use super::{exp::Exp, AppState};
+use super::{exp::Exp, AppState, ServerMember};
+use crate::db::dao;
+use core::convert::identity as id;
 use serenity::model::prelude::UserId;
 
 pub(super) fn add_signed_exp(
@@ -11,7 +13,19 @@ pub(super) fn add_signed_exp(
         .iter_mut()
         .find(|server_member| server_member.discord_id == discord_id)?;
     let old_exp: i64 = server_member.exp.to_i64();
-    let new_exp: Exp = Exp::from_i64(old_exp + delta);
+    // exp never goes negative
+    let new_exp: Exp = Exp::from_i64(old_exp.saturating_add(delta).max(0));
     server_member.exp = new_exp;
     Some(new_exp)
 }
+
+/// Caches a member who joined the server after the app state was loaded.
+pub(super) fn add_member(app_state: &mut AppState, discord_id: UserId, exp: i64) {
+    #[allow(clippy::cast_possible_wrap)]
+    let dao = dao::ServerMember {
+        discord_id: id::<u64>(discord_id.0) as i64,
+        exp,
+    };
+    let server_member = ServerMember::new(dao, &app_state.sorted_earned_roles);
+    app_state.users.push(server_member);
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serenity::model::prelude::{RoleId, UserId};
+
+use super::{locate_on_ladder, EarnedRole, ServerMember};
+
+/// The way the earned roles of a member accumulate as they climb the ladder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum EarnedRoleMode {
+    /// A promotion replaces the previous earned role, so members have only the highest one.
+    #[default]
+    Replacing,
+    /// Members keep every earned role that they have reached.
+    Cumulative,
+}
+
+impl EarnedRoleMode {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            EarnedRoleMode::Replacing => "replacing",
+            EarnedRoleMode::Cumulative => "cumulative",
+        }
+    }
+}
+
+impl FromStr for EarnedRoleMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replacing" => Ok(EarnedRoleMode::Replacing),
+            "cumulative" => Ok(EarnedRoleMode::Cumulative),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns the earned roles that a member at the given position on the ladder should have.
+pub(super) fn deserved_roles(
+    sorted_earned_roles: &[EarnedRole],
+    earned_role_idx: Option<usize>,
+    mode: EarnedRoleMode,
+) -> Vec<RoleId> {
+    let Some(idx) = earned_role_idx else {
+        return Vec::new();
+    };
+    match mode {
+        EarnedRoleMode::Replacing => sorted_earned_roles
+            .get(idx)
+            .map(|r| r.role_id)
+            .into_iter()
+            .collect(),
+        EarnedRoleMode::Cumulative => sorted_earned_roles
+            .iter()
+            .take(idx + 1)
+            .map(|r| r.role_id)
+            .collect(),
+    }
+}
+
+/// The changes of the Discord roles of a member that bring them in line
+/// with the ladder of earned roles.
+#[derive(Debug)]
+pub(crate) struct EarnedRoleChange {
+    pub(crate) user_id: UserId,
+    /// The earned roles that the member should have but doesn't.
+    pub(crate) add: Vec<RoleId>,
+    /// The earned roles that the member has but shouldn't.
+    pub(crate) remove: Vec<RoleId>,
+}
+
+/// Recomputes `earned_role_idx` and `nxt_exp_milestone` of every member
+/// from the ladder of earned roles.
+pub(super) fn relocate_members(sorted_earned_roles: &[EarnedRole], users: &mut [ServerMember]) {
+    for sm in users {
+        let (earned_role_idx, nxt_exp_milestone) = locate_on_ladder(sm.exp, sorted_earned_roles);
+        sm.earned_role_idx = earned_role_idx;
+        sm.nxt_exp_milestone = nxt_exp_milestone;
+    }
+}
+
+/// Compares the earned role that every member should have according to their exp
+/// with the roles that they actually have.
+///
+/// `member_roles` are the actual roles of the members. Members without an entry
+/// are not on the server anymore and are skipped. `retired_roles` are the roles that
+/// were earned roles before, so nobody should have them anymore.
+pub(super) fn plan_earned_roles(
+    sorted_earned_roles: &[EarnedRole],
+    users: &[ServerMember],
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+    retired_roles: &[RoleId],
+    mode: EarnedRoleMode,
+) -> Vec<EarnedRoleChange> {
+    users
+        .iter()
+        .filter_map(|sm| {
+            let roles: &Vec<RoleId> = member_roles.get(&sm.discord_id)?;
+            let (earned_role_idx, _) = locate_on_ladder(sm.exp, sorted_earned_roles);
+            let deserved: Vec<RoleId> = deserved_roles(sorted_earned_roles, earned_role_idx, mode);
+
+            let add: Vec<RoleId> = deserved
+                .iter()
+                .copied()
+                .filter(|role_id| !roles.contains(role_id))
+                .collect();
+            let remove: Vec<RoleId> = sorted_earned_roles
+                .iter()
+                .map(|r| r.role_id)
+                .chain(retired_roles.iter().copied())
+                .filter(|role_id| !deserved.contains(role_id) && roles.contains(role_id))
+                .collect();
+            if add.is_empty() && remove.is_empty() {
+                return None;
+            }
+            Some(EarnedRoleChange {
+                user_id: sm.discord_id,
+                add,
+                remove,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::exp::Exp;
+
+    fn ladder() -> Vec<EarnedRole> {
+        [(10, 100), (20, 200), (30, 300)]
+            .into_iter()
+            .map(|(role_id, exp_needed)| EarnedRole {
+                role_id: RoleId(role_id),
+                exp_needed: Exp(exp_needed),
+            })
+            .collect()
+    }
+
+    fn member(user_id: u64, exp: u64) -> ServerMember {
+        ServerMember {
+            discord_id: UserId(user_id),
+            exp: Exp(exp),
+            earned_role_idx: None,
+            nxt_exp_milestone: None,
+        }
+    }
+
+    fn plan(
+        users: &[ServerMember],
+        member_roles: &HashMap<UserId, Vec<RoleId>>,
+        retired_roles: &[RoleId],
+        mode: EarnedRoleMode,
+    ) -> Vec<EarnedRoleChange> {
+        plan_earned_roles(&ladder(), users, member_roles, retired_roles, mode)
+    }
+
+    #[test]
+    fn deserved_roles_depend_on_the_mode() {
+        let ladder = ladder();
+        assert!(deserved_roles(&ladder, None, EarnedRoleMode::Replacing).is_empty());
+        assert!(deserved_roles(&ladder, None, EarnedRoleMode::Cumulative).is_empty());
+        assert_eq!(
+            deserved_roles(&ladder, Some(1), EarnedRoleMode::Replacing),
+            vec![RoleId(20)]
+        );
+        assert_eq!(
+            deserved_roles(&ladder, Some(1), EarnedRoleMode::Cumulative),
+            vec![RoleId(10), RoleId(20)]
+        );
+    }
+
+    #[test]
+    fn up_to_date_members_are_not_changed() {
+        let users = [member(1, 150), member(2, 50)];
+        let member_roles = HashMap::from([
+            (UserId(1), vec![RoleId(10), RoleId(99)]),
+            (UserId(2), vec![]),
+        ]);
+        assert!(plan(&users, &member_roles, &[], EarnedRoleMode::Replacing).is_empty());
+    }
+
+    #[test]
+    fn several_tiers_are_skipped_at_once() {
+        let users = [member(1, 350)];
+        let member_roles = HashMap::from([(UserId(1), vec![RoleId(10)])]);
+        let changes = plan(&users, &member_roles, &[], EarnedRoleMode::Replacing);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].add, vec![RoleId(30)]);
+        assert_eq!(changes[0].remove, vec![RoleId(10)]);
+    }
+
+    #[test]
+    fn members_below_the_first_tier_lose_all_earned_roles() {
+        let users = [member(1, 0)];
+        let member_roles = HashMap::from([(UserId(1), vec![RoleId(20), RoleId(30)])]);
+        let changes = plan(&users, &member_roles, &[], EarnedRoleMode::Replacing);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].add.is_empty());
+        assert_eq!(changes[0].remove, vec![RoleId(20), RoleId(30)]);
+    }
+
+    #[test]
+    fn retired_roles_are_taken_away() {
+        let users = [member(1, 200)];
+        let member_roles = HashMap::from([(UserId(1), vec![RoleId(20), RoleId(40)])]);
+        let changes = plan(
+            &users,
+            &member_roles,
+            &[RoleId(40)],
+            EarnedRoleMode::Replacing,
+        );
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].add.is_empty());
+        assert_eq!(changes[0].remove, vec![RoleId(40)]);
+    }
+
+    #[test]
+    fn members_without_roles_are_skipped() {
+        let users = [member(1, 200), member(2, 200)];
+        let member_roles = HashMap::from([(UserId(2), vec![])]);
+        let changes = plan(&users, &member_roles, &[], EarnedRoleMode::Replacing);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].user_id, UserId(2));
+        assert_eq!(changes[0].add, vec![RoleId(20)]);
+    }
+
+    #[test]
+    fn cumulative_mode_gives_every_reached_role() {
+        let users = [member(1, 350)];
+        let member_roles = HashMap::from([(UserId(1), vec![RoleId(20)])]);
+        let changes = plan(&users, &member_roles, &[], EarnedRoleMode::Cumulative);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].add, vec![RoleId(10), RoleId(30)]);
+        assert!(changes[0].remove.is_empty());
+    }
+
+    #[test]
+    fn cumulative_mode_takes_away_unreached_roles() {
+        let users = [member(1, 150)];
+        let member_roles = HashMap::from([(UserId(1), vec![RoleId(10), RoleId(20), RoleId(30)])]);
+        let changes = plan(&users, &member_roles, &[], EarnedRoleMode::Cumulative);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].add.is_empty());
+        assert_eq!(changes[0].remove, vec![RoleId(20), RoleId(30)]);
+    }
+
+    #[test]
+    fn replacing_mode_takes_away_the_lower_roles() {
+        let users = [member(1, 250)];
+        let member_roles = HashMap::from([(UserId(1), vec![RoleId(10), RoleId(20)])]);
+        let changes = plan(&users, &member_roles, &[], EarnedRoleMode::Replacing);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].add.is_empty());
+        assert_eq!(changes[0].remove, vec![RoleId(10)]);
+    }
+}
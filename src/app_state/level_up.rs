@@ -0,0 +1,242 @@
+//! Announcements of the promotions of members to a higher earned role.
+
+use std::fmt::{self, Display};
+
+use serenity::{
+    http::Http,
+    model::prelude::{ChannelId, RoleId, UserId},
+};
+
+use super::exp::Exp;
+use crate::db::dao;
+
+/// Runtime-configurable settings of the level-up announcements.
+///
+/// The settings are stored in the single row of the `level_up_settings` table.
+#[derive(Debug, Clone)]
+pub(crate) struct LevelUpSettings {
+    /// Whether the promotions are announced at all.
+    pub(crate) enabled: bool,
+    /// The channel for the announcements. The bot channel is used if it's not set.
+    pub(crate) channel: Option<ChannelId>,
+    /// The text of the announcements with [`LevelUpSettings::PLACEHOLDERS`].
+    pub(crate) template: String,
+}
+
+/// A change of one of the [`LevelUpSettings`].
+#[derive(Debug, Clone)]
+pub(crate) enum LevelUpSetting {
+    Enabled(bool),
+    Channel(Option<ChannelId>),
+    Template(String),
+}
+
+/// A promotion of a member to a higher earned role.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LevelUp {
+    pub(crate) user_id: UserId,
+    pub(crate) role_id: RoleId,
+    pub(crate) exp: Exp,
+    /// The exp needed for the next earned role, if there is one.
+    pub(crate) next_milestone: Option<Exp>,
+}
+
+/// A rendered announcement of a promotion that is yet to be posted.
+#[derive(Debug)]
+pub(crate) struct LevelUpAnnouncement {
+    channel: ChannelId,
+    content: String,
+    user_id: UserId,
+    /// Whether the promoted member is pinged.
+    ping: bool,
+}
+
+impl LevelUp {
+    /// The promotion with the longest ids and amounts of exp, which makes
+    /// the longest announcement.
+    const LONGEST: LevelUp = LevelUp {
+        user_id: UserId(u64::MAX),
+        role_id: RoleId(u64::MAX),
+        exp: Exp(u64::MAX),
+        next_milestone: Some(Exp(u64::MAX)),
+    };
+}
+
+impl LevelUpAnnouncement {
+    /// Posts the announcement. Only the promoted member can be pinged, and only if they
+    /// haven't opted out.
+    ///
+    /// The failure to post is logged, since the promotion itself has already happened.
+    pub(crate) async fn post(self, http: &Http) {
+        let LevelUpAnnouncement {
+            channel,
+            content,
+            user_id,
+            ping,
+        } = self;
+        if let Err(e) = channel
+            .send_message(http, |m| {
+                m.content(content).allowed_mentions(|am| {
+                    am.empty_parse();
+                    if ping {
+                        am.users([user_id]);
+                    }
+                    am
+                })
+            })
+            .await
+        {
+            eprintln!("Failed to announce the promotion of {user_id}: {e}");
+        }
+    }
+}
+
+impl LevelUpSetting {
+    pub(crate) const NAMES: &'static [&'static str] = &["enabled", "channel", "template"];
+
+    /// The longest message that Discord accepts.
+    const MAX_MSG_CHARS: usize = 2000;
+
+    /// Parses the setting from its name and the value given by a command.
+    pub(crate) fn parse(name: &str, value: &str) -> Result<Self, String> {
+        match name {
+            "enabled" => match value {
+                "on" | "true" | "yes" => Ok(LevelUpSetting::Enabled(true)),
+                "off" | "false" | "no" => Ok(LevelUpSetting::Enabled(false)),
+                _ => Err("The value must be either `on` or `off`.".to_string()),
+            },
+            "channel" if value == "default" => Ok(LevelUpSetting::Channel(None)),
+            "channel" => value
+                .parse::<ChannelId>()
+                .map(|channel_id| LevelUpSetting::Channel(Some(channel_id)))
+                .map_err(|_| "The channel must be a channel mention or `default`.".to_string()),
+            "template" => {
+                let template = value.trim();
+                if template.is_empty() {
+                    Err("The template can't be empty.".to_string())
+                } else if render(template, &LevelUp::LONGEST).chars().count() > Self::MAX_MSG_CHARS
+                {
+                    Err(format!(
+                        "The template can't be longer than {} characters \
+                        once the placeholders are filled in.",
+                        Self::MAX_MSG_CHARS
+                    ))
+                } else {
+                    Ok(LevelUpSetting::Template(template.to_string()))
+                }
+            }
+            _ => Err(format!(
+                "Unknown setting. Try one of: `{}`.",
+                Self::NAMES.join("`, `")
+            )),
+        }
+    }
+}
+
+impl Display for LevelUpSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelUpSetting::Enabled(enabled) => {
+                let state = if *enabled { "on" } else { "off" };
+                write!(f, "`enabled`: {state}")
+            }
+            LevelUpSetting::Channel(Some(channel_id)) => write!(f, "`channel`: <#{channel_id}>"),
+            LevelUpSetting::Channel(None) => write!(f, "`channel`: the bot channel"),
+            LevelUpSetting::Template(template) => write!(f, "`template`: {template}"),
+        }
+    }
+}
+
+impl LevelUpSettings {
+    /// The placeholders that are replaced in the template.
+    pub(crate) const PLACEHOLDERS: &'static [&'static str] =
+        &["{member}", "{role}", "{exp}", "{next}"];
+
+    pub(crate) fn apply(&mut self, setting: LevelUpSetting) {
+        match setting {
+            LevelUpSetting::Enabled(enabled) => self.enabled = enabled,
+            LevelUpSetting::Channel(channel) => self.channel = channel,
+            LevelUpSetting::Template(template) => self.template = template,
+        }
+    }
+
+    /// Returns the current values of all settings.
+    pub(crate) fn to_settings(&self) -> Vec<LevelUpSetting> {
+        vec![
+            LevelUpSetting::Enabled(self.enabled),
+            LevelUpSetting::Channel(self.channel),
+            LevelUpSetting::Template(self.template.clone()),
+        ]
+    }
+
+    /// Renders the announcement of the promotion, unless the announcements are disabled.
+    ///
+    /// `bot_channel` is used if no channel is set for the announcements.
+    pub(crate) fn announcement(
+        &self,
+        bot_channel: ChannelId,
+        level_up: &LevelUp,
+        ping: bool,
+    ) -> Option<LevelUpAnnouncement> {
+        if !self.enabled {
+            return None;
+        }
+        Some(LevelUpAnnouncement {
+            channel: self.channel.unwrap_or(bot_channel),
+            content: self.render(level_up),
+            user_id: level_up.user_id,
+            ping,
+        })
+    }
+
+    /// Fills in the placeholders of the template.
+    ///
+    /// The member and the role are filled in as mentions. Whether they ping anyone
+    /// is decided by the allowed mentions of the message.
+    pub(crate) fn render(&self, level_up: &LevelUp) -> String {
+        render(&self.template, level_up)
+    }
+}
+
+fn render(template: &str, level_up: &LevelUp) -> String {
+    let next: String = match level_up.next_milestone {
+        Some(next_milestone) => next_milestone.0.to_string(),
+        None => "none".to_string(),
+    };
+    template
+        .replace("{member}", &format!("<@{}>", level_up.user_id))
+        .replace("{role}", &format!("<@&{}>", level_up.role_id))
+        .replace("{exp}", &level_up.exp.0.to_string())
+        .replace("{next}", &next)
+}
+
+impl From<dao::LevelUpSettings> for LevelUpSettings {
+    fn from(dao: dao::LevelUpSettings) -> Self {
+        let dao::LevelUpSettings {
+            enabled,
+            channel_id,
+            template,
+        } = dao;
+        #[allow(clippy::cast_sign_loss)]
+        let channel = channel_id.map(|channel_id| ChannelId(channel_id as u64));
+        Self {
+            enabled,
+            channel,
+            template,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_is_bounded_once_rendered() {
+        assert!(LevelUpSetting::parse("template", &"a".repeat(2000)).is_ok());
+        assert!(LevelUpSetting::parse("template", &"a".repeat(2001)).is_err());
+        // Every `{member}` can become a mention of 23 characters
+        assert!(LevelUpSetting::parse("template", &"{member}".repeat(86)).is_ok());
+        assert!(LevelUpSetting::parse("template", &"{member}".repeat(87)).is_err());
+    }
+}
@@ -1,28 +1,57 @@
-use std::convert::identity as id;
+use std::{collections::HashSet, convert::identity as id};
 
 use serenity::model::prelude::{Member, RoleId, UserId};
 use sqlx::PgPool;
 
-use crate::db::{self, dao};
+use crate::{
+    db::{self, dao},
+    rank_card::theme::Theme,
+};
 
 use self::{exp::Exp, reqd_prompts::ReqdPrompts};
 
+pub(crate) mod event;
 pub(crate) mod exp;
+pub(crate) mod exp_rules;
 mod in_cache;
+pub(crate) mod ladder;
+pub(crate) mod level_up;
 mod membership;
+pub(crate) mod rank;
 pub(crate) mod reqd_prompts;
-mod roles;
+pub(crate) mod roles;
+pub(crate) mod season;
+pub(crate) mod streak;
 pub(crate) mod sync;
 pub(crate) mod type_map_keys;
+pub(crate) mod voice;
 
+use event::ExpEvents;
+use exp_rules::{ExpMultipliers, ExpSettings, MsgActivity};
+use ladder::EarnedRoleMode;
+use level_up::LevelUpSettings;
 use roles::SelfRoleMsgs;
+use season::SeasonSettings;
+use streak::StreakSettings;
+use voice::VoiceActivity;
 
 pub(crate) struct AppState {
     pub(crate) users: Vec<ServerMember>,
     pub(crate) reqd_prompts: ReqdPrompts,
     pub(crate) sorted_earned_roles: Vec<EarnedRole>,
-    #[allow(dead_code)]
     pub(crate) self_role_msgs: SelfRoleMsgs,
+    pub(crate) exp_settings: ExpSettings,
+    pub(crate) exp_multipliers: ExpMultipliers,
+    pub(crate) msg_activity: MsgActivity,
+    pub(crate) voice_activity: VoiceActivity,
+    pub(crate) rank_card_theme: Theme,
+    pub(crate) earned_role_mode: EarnedRoleMode,
+    pub(crate) level_up_settings: LevelUpSettings,
+    /// The members who don't want to be pinged by the level-up announcements.
+    pub(crate) level_up_ping_opt_outs: HashSet<UserId>,
+    pub(crate) season_settings: SeasonSettings,
+    pub(crate) streak_settings: StreakSettings,
+    pub(crate) exp_events: ExpEvents,
 }
 
 /// For database operations, [`ServerMember`] is converted to [`crate::db::dao::ServerMember`].
@@ -49,25 +78,7 @@ impl ServerMember {
         let discord_id = UserId(discord_id);
         let exp: Exp = Exp::from_i64(exp);
 
-        let earned_role_idx = match sorted_earned_roles.binary_search_by_key(&exp, |r| r.exp_needed)
-        {
-            Ok(pos) => Some(pos),
-            Err(pos) => {
-                if pos == 0 {
-                    None
-                } else {
-                    Some(pos - 1)
-                }
-            }
-        };
-
-        let nxt_exp_milestone = if let Some(earned_role_idx) = earned_role_idx {
-            sorted_earned_roles
-                .get(earned_role_idx + 1)
-                .map(|r| r.exp_needed)
-        } else {
-            None
-        };
+        let (earned_role_idx, nxt_exp_milestone) = locate_on_ladder(exp, sorted_earned_roles);
 
         ServerMember {
             discord_id,
@@ -78,6 +89,53 @@ impl ServerMember {
     }
 }
 
+/// Finds the index of the highest earned role that the amount of exp is enough for
+/// and the exp needed for the next earned role.
+fn locate_on_ladder(exp: Exp, sorted_earned_roles: &[EarnedRole]) -> (Option<usize>, Option<Exp>) {
+    let earned_role_idx = match sorted_earned_roles.binary_search_by_key(&exp, |r| r.exp_needed) {
+        Ok(pos) => Some(pos),
+        Err(pos) => {
+            if pos == 0 {
+                None
+            } else {
+                Some(pos - 1)
+            }
+        }
+    };
+    let nxt_exp_milestone = sorted_earned_roles
+        .get(earned_role_idx.map_or(0, |idx| idx + 1))
+        .map(|r| r.exp_needed);
+    (earned_role_idx, nxt_exp_milestone)
+}
+
+impl EarnedRole {
+    pub(crate) fn new(role_id: RoleId, exp_needed: Exp) -> Self {
+        Self {
+            role_id,
+            exp_needed,
+        }
+    }
+
+    pub(crate) fn role_id(&self) -> RoleId {
+        self.role_id
+    }
+}
+
+/// Returns the earned role, other than `except`, that needs exactly `exp_needed` exp.
+///
+/// Two earned roles can't share a threshold, so such a role blocks
+/// giving the threshold to another role.
+pub(crate) fn role_with_threshold(
+    sorted_earned_roles: &[EarnedRole],
+    exp_needed: Exp,
+    except: Option<RoleId>,
+) -> Option<RoleId> {
+    sorted_earned_roles
+        .iter()
+        .find(|r| r.exp_needed == exp_needed && Some(r.role_id) != except)
+        .map(|r| r.role_id)
+}
+
 impl From<dao::EarnedRole> for EarnedRole {
     fn from(value: dao::EarnedRole) -> Self {
         let dao::EarnedRole {
@@ -98,18 +156,91 @@ impl From<dao::EarnedRole> for EarnedRole {
 }
 
 impl AppState {
-    pub(crate) async fn new(pool: &PgPool, fetched_members: Vec<Member>) -> Self {
+    /// Loads the state of the app.
+    ///
+    /// Returns the descriptions of the invalid rows of the database that were skipped
+    /// along with the state.
+    pub(crate) async fn new(pool: &PgPool, fetched_members: Vec<Member>) -> (Self, Vec<String>) {
         let db_members = db::server_members(pool).await.unwrap_or_else(|e| {
             panic!("Sqlx failure when querying the list of server members: {e}");
         });
 
-        let self_role_msgs: SelfRoleMsgs = db::sorted_self_assigned_roles(pool)
+        let (self_role_msgs, invalid_rows): (SelfRoleMsgs, Vec<String>) = SelfRoleMsgs::load(
+            db::sorted_self_assigned_roles(pool)
+                .await
+                .unwrap_or_else(|e| {
+                    panic!("Sqlx failure when querying the list of self-assigned roles: {e}");
+                }),
+        );
+
+        let exp_settings: ExpSettings = db::exp_settings(pool)
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the experience settings: {e}");
+            })
+            .into();
+
+        let exp_multipliers = ExpMultipliers::new(
+            db::channel_exp_multipliers(pool).await.unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the channel exp multipliers: {e}");
+            }),
+            db::role_exp_multipliers(pool).await.unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the role exp multipliers: {e}");
+            }),
+        );
+
+        let rank_card_theme: Theme = db::rank_card_theme(pool)
             .await
             .unwrap_or_else(|e| {
-                panic!("Sqlx failure when querying the list of self-assigned roles: {e}");
+                panic!("Sqlx failure when querying the rank card theme: {e}");
             })
             .into();
 
+        let earned_role_mode: EarnedRoleMode = db::earned_role_mode(pool)
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the earned role mode: {e}");
+            })
+            .parse()
+            .unwrap_or_else(|()| panic!("The earned role mode in the database is invalid"));
+
+        let level_up_settings: LevelUpSettings = db::level_up_settings(pool)
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the level-up settings: {e}");
+            })
+            .into();
+
+        #[allow(clippy::cast_sign_loss)]
+        let level_up_ping_opt_outs: HashSet<UserId> = db::level_up_ping_opt_outs(pool)
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the level-up ping opt-outs: {e}");
+            })
+            .into_iter()
+            .map(|discord_id| UserId(discord_id as u64))
+            .collect();
+
+        let season_settings: SeasonSettings = db::season_settings(pool)
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the season settings: {e}");
+            })
+            .into();
+
+        let streak_settings = StreakSettings::new(
+            db::streak_timezone(pool).await.unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the streak timezone: {e}");
+            }),
+            db::streak_bonuses(pool).await.unwrap_or_else(|e| {
+                panic!("Sqlx failure when querying the streak bonuses: {e}");
+            }),
+        );
+
+        let exp_events = ExpEvents::new(db::exp_events(pool).await.unwrap_or_else(|e| {
+            panic!("Sqlx failure when querying the exp events: {e}");
+        }));
+
         let sorted_earned_roles = db::sorted_earned_roles(pool)
             .await
             .unwrap_or_else(|e| {
@@ -128,11 +259,74 @@ impl AppState {
             .collect();
         let reqd_prompts = ReqdPrompts::default();
 
-        AppState {
+        let app_state = AppState {
             users,
             reqd_prompts,
             sorted_earned_roles,
             self_role_msgs,
-        }
+            exp_settings,
+            exp_multipliers,
+            msg_activity: MsgActivity::default(),
+            voice_activity: VoiceActivity::default(),
+            rank_card_theme,
+            earned_role_mode,
+            level_up_settings,
+            level_up_ping_opt_outs,
+            season_settings,
+            streak_settings,
+            exp_events,
+        };
+        (app_state, invalid_rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ladder() -> Vec<EarnedRole> {
+        [(10, 100), (20, 200), (30, 300)]
+            .into_iter()
+            .map(|(role_id, exp_needed)| EarnedRole {
+                role_id: RoleId(role_id),
+                exp_needed: Exp(exp_needed),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exact_threshold_reaches_the_tier() {
+        assert_eq!(
+            locate_on_ladder(Exp(200), &ladder()),
+            (Some(1), Some(Exp(300)))
+        );
+        assert_eq!(
+            locate_on_ladder(Exp(199), &ladder()),
+            (Some(0), Some(Exp(200)))
+        );
+    }
+
+    #[test]
+    fn below_the_first_tier() {
+        assert_eq!(locate_on_ladder(Exp(0), &ladder()), (None, Some(Exp(100))));
+        assert_eq!(locate_on_ladder(Exp(0), &[]), (None, None));
+    }
+
+    #[test]
+    fn above_the_last_tier() {
+        assert_eq!(locate_on_ladder(Exp(300), &ladder()), (Some(2), None));
+        assert_eq!(locate_on_ladder(Exp(u64::MAX), &ladder()), (Some(2), None));
+    }
+
+    #[test]
+    fn several_tiers_are_crossed_at_once() {
+        // Promotion from below the first tier to the last one
+        assert_eq!(locate_on_ladder(Exp(50), &ladder()).0, None);
+        assert_eq!(locate_on_ladder(Exp(350), &ladder()).0, Some(2));
+        // Demotion from the last tier to the first one
+        assert_eq!(
+            locate_on_ladder(Exp(150), &ladder()),
+            (Some(0), Some(Exp(200)))
+        );
     }
 }
@@ -0,0 +1,98 @@
+use serenity::model::prelude::{RoleId, UserId};
+
+use super::{exp::Exp, AppState, EarnedRole};
+
+/// An earned role together with the experience points needed for it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RankRole {
+    pub(crate) role_id: RoleId,
+    pub(crate) exp_needed: Exp,
+}
+
+/// The standing of a member on the ladder of earned roles.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rank {
+    pub(crate) exp: Exp,
+    /// The highest earned role the member has reached, if any.
+    pub(crate) earned_role: Option<RankRole>,
+    /// The earned role the member is going to reach next, if any.
+    pub(crate) next_role: Option<RankRole>,
+}
+
+impl From<&EarnedRole> for RankRole {
+    fn from(earned_role: &EarnedRole) -> Self {
+        RankRole {
+            role_id: earned_role.role_id,
+            exp_needed: earned_role.exp_needed,
+        }
+    }
+}
+
+impl Rank {
+    /// Returns the experience points that are missing until the next role, if there is one.
+    pub(crate) fn exp_remaining(&self) -> Option<Exp> {
+        let Exp(exp) = self.exp;
+        self.next_role
+            .map(|next_role| Exp(next_role.exp_needed.0.saturating_sub(exp)))
+    }
+
+    /// Returns the progress from the current earned role (or from zero) to the next one
+    /// as a fraction between 0 and 1. Without the next role, the progress is complete.
+    pub(crate) fn progress(&self) -> f64 {
+        let Some(next_role) = self.next_role else {
+            return 1.0;
+        };
+        let Exp(start) = self.earned_role.map_or(Exp(0), |r| r.exp_needed);
+        let Exp(end) = next_role.exp_needed;
+        let Exp(exp) = self.exp;
+        if end <= start {
+            return 1.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let progress = exp.saturating_sub(start) as f64 / (end - start) as f64;
+        progress.clamp(0.0, 1.0)
+    }
+}
+
+impl AppState {
+    /// Returns the earned roles ordered by the exp needed for them.
+    pub(crate) fn earned_roles(&self) -> Vec<RankRole> {
+        self.sorted_earned_roles
+            .iter()
+            .map(RankRole::from)
+            .collect()
+    }
+
+    /// Returns the highest earned role that the amount of exp is enough for.
+    pub(crate) fn earned_role_for_exp(&self, exp: Exp) -> Option<RankRole> {
+        let reached = self
+            .sorted_earned_roles
+            .partition_point(|earned_role| earned_role.exp_needed <= exp);
+        reached
+            .checked_sub(1)
+            .and_then(|idx| self.sorted_earned_roles.get(idx))
+            .map(RankRole::from)
+    }
+
+    /// Returns the rank of the member, if the member is tracked.
+    pub(crate) fn rank(&self, user_id: UserId) -> Option<Rank> {
+        let server_member = self
+            .users
+            .iter()
+            .find(|server_member| server_member.discord_id == user_id)?;
+        let earned_role_idx = server_member.earned_role_idx;
+        let earned_role: Option<RankRole> = earned_role_idx
+            .and_then(|idx| self.sorted_earned_roles.get(idx))
+            .map(RankRole::from);
+        let next_role_idx = earned_role_idx.map_or(0, |idx| idx + 1);
+        let next_role: Option<RankRole> = self
+            .sorted_earned_roles
+            .get(next_role_idx)
+            .map(RankRole::from);
+        Some(Rank {
+            exp: server_member.exp,
+            earned_role,
+            next_role,
+        })
+    }
+}
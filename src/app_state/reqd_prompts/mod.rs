@@ -2,7 +2,7 @@ use std::ops::ControlFlow;
 
 use serenity::prelude::Context;
 
-use crate::app_state;
+use crate::app_state::{self, ladder::EarnedRoleMode};
 use crate::{bots::MainBot, commands::role::EarnedRolePromptReq};
 use serenity::model::prelude::Message;
 
@@ -22,9 +22,18 @@ impl ReqdPrompts {
         msg: &Message,
         sorted_earned_roles: &mut Vec<app_state::EarnedRole>,
         users: &mut Vec<app_state::ServerMember>,
+        earned_role_mode: EarnedRoleMode,
     ) -> ControlFlow<()> {
-        EarnedRolePromptReq::handle_if_pending(bot, ctx, msg, sorted_earned_roles, self, users)
-            .await?;
+        EarnedRolePromptReq::handle_if_pending(
+            bot,
+            ctx,
+            msg,
+            sorted_earned_roles,
+            self,
+            users,
+            earned_role_mode,
+        )
+        .await?;
         ControlFlow::Continue(())
     }
 }
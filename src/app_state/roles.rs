@@ -1,7 +1,7 @@
 use core::convert::identity as id;
 use itertools::Itertools;
-use serenity::model::prelude::{EmojiId, MessageId, RoleId};
-use std::collections::HashMap;
+use serenity::model::prelude::{EmojiId, MessageId, ReactionType, RoleId};
+use std::{collections::HashMap, str::FromStr};
 
 use crate::db::dao;
 
@@ -11,23 +11,229 @@ enum Emoji {
     BuiltIn(String),
 }
 
+#[derive(Debug)]
+struct SelfRole {
+    emoji: Emoji,
+    /// id of group of mutually exclusive roles
+    excl_role_group_id: i64,
+}
+
 // TODO: consider the structure with aggregated enum vs enum with structure variants
 #[derive(Debug)]
 enum SelfRoleMsgData {
     /// User can select only one role.
-    ChoiceGroup(HashMap<RoleId, Emoji>),
+    ChoiceGroup(HashMap<RoleId, SelfRole>),
     /// User can select arbitrary subset of the roles.
-    RoleGroup(HashMap<RoleId, Emoji>),
+    RoleGroup(HashMap<RoleId, SelfRole>),
+}
+
+/// The way the self-role message offers its roles to the members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Presentation {
+    /// Members react to the message with the emojis of the roles.
+    #[default]
+    Reactions,
+    /// Members use buttons or, for choice groups, a dropdown attached to the message.
+    Components,
 }
 
 #[derive(Debug)]
-pub(crate) struct SelfRoleMsgs(HashMap<MessageId, SelfRoleMsgData>);
+struct SelfRoleMsg {
+    presentation: Presentation,
+    data: SelfRoleMsgData,
+}
+
+/// A role that can be self-assigned via a self-role message.
+pub(crate) struct SelfRoleEntry {
+    pub(crate) message_id: MessageId,
+    pub(crate) role_id: RoleId,
+    pub(crate) reaction: ReactionType,
+    pub(crate) excl_role_group_id: i64,
+    pub(crate) presentation: Presentation,
+}
+
+impl Presentation {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Presentation::Reactions => "reactions",
+            Presentation::Components => "components",
+        }
+    }
+
+    /// Returns the number of roles that a message can offer this way.
+    ///
+    /// Discord allows at most 20 distinct reactions on a message,
+    /// and at most 25 buttons or dropdown options.
+    pub(crate) fn max_roles(self) -> usize {
+        match self {
+            Presentation::Reactions => 20,
+            Presentation::Components => 25,
+        }
+    }
+}
 
-impl From<Vec<dao::SelfAssignedRole>> for SelfRoleMsgs {
-    fn from(self_assigned_roles: Vec<dao::SelfAssignedRole>) -> Self {
-        let mut msgs: HashMap<MessageId, SelfRoleMsgData> = HashMap::new();
+impl FromStr for Presentation {
+    type Err = ();
 
-        let it = self_assigned_roles.into_iter();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reactions" => Ok(Presentation::Reactions),
+            "components" => Ok(Presentation::Components),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Emoji {
+    /// Checks whether the reaction uses this emoji.
+    fn matches(&self, reaction: &ReactionType) -> bool {
+        match (self, reaction) {
+            (Emoji::Custom(emoji_id), ReactionType::Custom { id, .. }) => emoji_id == id,
+            (Emoji::BuiltIn(name), ReactionType::Unicode(unicode)) => name == unicode,
+            _ => false,
+        }
+    }
+
+    fn to_reaction(&self) -> ReactionType {
+        match self {
+            Emoji::Custom(emoji_id) => ReactionType::Custom {
+                animated: false,
+                id: *emoji_id,
+                // Discord identifies custom emojis by their id, the name is a mere placeholder
+                name: Some("_".to_string()),
+            },
+            Emoji::BuiltIn(name) => ReactionType::Unicode(name.clone()),
+        }
+    }
+}
+
+impl SelfRoleMsgData {
+    fn roles(&self) -> &HashMap<RoleId, SelfRole> {
+        match self {
+            SelfRoleMsgData::ChoiceGroup(roles) | SelfRoleMsgData::RoleGroup(roles) => roles,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SelfRoleMsgs(HashMap<MessageId, SelfRoleMsg>);
+
+impl SelfRoleMsgs {
+    /// Returns the role that is assigned by reacting to the message
+    /// with the given emoji, if the message is a self-role message
+    /// that offers its roles via reactions.
+    pub(crate) fn role_for_reaction(
+        &self,
+        message_id: MessageId,
+        reaction: &ReactionType,
+    ) -> Option<RoleId> {
+        let Self(msgs) = self;
+        let msg: &SelfRoleMsg = msgs.get(&message_id)?;
+        if msg.presentation != Presentation::Reactions {
+            return None;
+        }
+        msg.data
+            .roles()
+            .iter()
+            .find(|(_role_id, self_role)| self_role.emoji.matches(reaction))
+            .map(|(role_id, _self_role)| *role_id)
+    }
+
+    /// Returns the way the self-role message offers its roles, if the message is tracked.
+    pub(crate) fn presentation(&self, message_id: MessageId) -> Option<Presentation> {
+        let Self(msgs) = self;
+        msgs.get(&message_id).map(|msg| msg.presentation)
+    }
+
+    /// Checks whether the member can hold only one role of the self-role message.
+    pub(crate) fn is_choice_group(&self, message_id: MessageId) -> bool {
+        let Self(msgs) = self;
+        matches!(
+            msgs.get(&message_id),
+            Some(SelfRoleMsg {
+                data: SelfRoleMsgData::ChoiceGroup(_),
+                ..
+            })
+        )
+    }
+
+    /// Returns the entry of the self-assigned role, if the role is self-assignable.
+    pub(crate) fn entry(&self, role_id: RoleId) -> Option<SelfRoleEntry> {
+        self.entries().find(|entry| entry.role_id == role_id)
+    }
+
+    /// Returns the entries of the self-assigned roles of the message, sorted by role.
+    pub(crate) fn msg_entries(&self, message_id: MessageId) -> Vec<SelfRoleEntry> {
+        self.entries()
+            .filter(|entry| entry.message_id == message_id)
+            .collect()
+    }
+
+    /// Returns the entries of all self-assigned roles, sorted by message and then by role.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = SelfRoleEntry> + '_ {
+        let Self(msgs) = self;
+        msgs.iter()
+            .flat_map(|(message_id, msg)| {
+                msg.data
+                    .roles()
+                    .iter()
+                    .map(move |(role_id, self_role)| SelfRoleEntry {
+                        message_id: *message_id,
+                        role_id: *role_id,
+                        reaction: self_role.emoji.to_reaction(),
+                        excl_role_group_id: self_role.excl_role_group_id,
+                        presentation: msg.presentation,
+                    })
+            })
+            .sorted_unstable_by_key(|entry| (entry.message_id, entry.role_id))
+    }
+
+    /// Returns the roles that share the group of mutually exclusive roles with the given role.
+    ///
+    /// The roles of the same group can be spread across several self-role messages.
+    pub(crate) fn excl_siblings(&self, role_id: RoleId) -> Vec<SelfRoleEntry> {
+        let Some(SelfRoleEntry {
+            excl_role_group_id, ..
+        }) = self.entry(role_id)
+        else {
+            return Vec::new();
+        };
+        self.entries()
+            .filter(|entry| {
+                entry.role_id != role_id && entry.excl_role_group_id == excl_role_group_id
+            })
+            .collect()
+    }
+}
+
+impl SelfRoleMsgs {
+    /// Builds the self-role messages from the rows of the `self_assigned_roles` table
+    /// sorted by `message_id`.
+    ///
+    /// Invalid rows are skipped. The descriptions of the skipped rows are returned
+    /// alongside so that they can be reported.
+    pub(crate) fn load(self_assigned_roles: Vec<dao::SelfAssignedRole>) -> (Self, Vec<String>) {
+        let mut msgs: HashMap<MessageId, SelfRoleMsg> = HashMap::new();
+        let mut invalid_rows: Vec<String> = Vec::new();
+
+        let it = self_assigned_roles.into_iter().filter(|r| {
+            let problem = match (r.emoji_id, &r.emoji_name) {
+                (Some(_), None) | (None, Some(_)) => None,
+                (Some(_), Some(_)) => Some("has both `emoji_id` and `emoji_name` set"),
+                (None, None) => Some("has neither `emoji_id` nor `emoji_name` set"),
+            };
+            match problem {
+                Some(problem) => {
+                    invalid_rows.push(format!(
+                        "The self-assigned role `{}` of the message `{}` {problem} \
+                        and was skipped.",
+                        r.role_id, r.message_id
+                    ));
+                    false
+                }
+                None => true,
+            }
+        });
 
         let mut group_buffer = Vec::<dao::SelfAssignedRole>::with_capacity(10);
         for (msg_id, group) in &it.group_by(|a| {
@@ -39,9 +245,14 @@ impl From<Vec<dao::SelfAssignedRole>> for SelfRoleMsgs {
                 .iter()
                 .map(|r| r.excl_role_group_id)
                 .all_equal();
+            // The presentation is the same for all roles of the message by construction
+            let presentation: Presentation = group_buffer
+                .first()
+                .and_then(|r| r.presentation.parse().ok())
+                .unwrap_or_default();
 
-            let mut data: HashMap<RoleId, Emoji> = HashMap::with_capacity(10);
-            let drain_it = group_buffer.drain(..).map(|r| {
+            let mut data: HashMap<RoleId, SelfRole> = HashMap::with_capacity(10);
+            let drain_it = group_buffer.drain(..).filter_map(|r| {
                 let role_id = id::<i64>(r.role_id) as u64;
                 let role_id = RoleId(role_id);
                 let emoji = match (r.emoji_id, r.emoji_name) {
@@ -51,9 +262,14 @@ impl From<Vec<dao::SelfAssignedRole>> for SelfRoleMsgs {
                         Emoji::Custom(emoji_id)
                     }
                     (None, Some(emoji_name)) => Emoji::BuiltIn(emoji_name),
-                    _ => unreachable!("Exactly one of emoji_id or emoji_name must be Some(_)"),
+                    // Such rows have been filtered out above
+                    _ => return None,
+                };
+                let self_role = SelfRole {
+                    emoji,
+                    excl_role_group_id: r.excl_role_group_id,
                 };
-                (role_id, emoji)
+                Some((role_id, self_role))
             });
             data.extend(drain_it);
             let data = if are_excl {
@@ -62,9 +278,9 @@ impl From<Vec<dao::SelfAssignedRole>> for SelfRoleMsgs {
                 SelfRoleMsgData::RoleGroup(data)
             };
 
-            msgs.insert(msg_id, data);
+            msgs.insert(msg_id, SelfRoleMsg { presentation, data });
         }
 
-        Self(msgs)
+        (Self(msgs), invalid_rows)
     }
 }
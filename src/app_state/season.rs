@@ -0,0 +1,134 @@
+//! Seasons, after which the exp of the members is reset or scaled down
+//! and the final standings are archived.
+
+use std::fmt::{self, Display};
+
+use super::{exp::Exp, ServerMember};
+use crate::db::dao;
+
+/// Runtime-configurable settings of the seasons.
+///
+/// The settings are stored in the single row of the `season_settings` table.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SeasonSettings {
+    /// The number of days after which a season ends on its own.
+    /// 0 means that the seasons are only ended by the admins.
+    pub(crate) length_days: u32,
+    /// The percentage of the exp that the members keep into the next season.
+    pub(crate) kept_percent: u32,
+}
+
+/// A change of one of the [`SeasonSettings`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SeasonSetting {
+    LengthDays(u32),
+    KeptPercent(u32),
+}
+
+impl SeasonSetting {
+    pub(crate) const NAMES: &'static [&'static str] = &["length", "keep"];
+
+    /// Parses the setting from its name and the value given by a command.
+    pub(crate) fn parse(name: &str, value: &str) -> Result<Self, String> {
+        match name {
+            "length" => value
+                .parse::<u32>()
+                .map(SeasonSetting::LengthDays)
+                .map_err(|_| "The length must be a whole number of days.".to_string()),
+            "keep" => match value.trim_end_matches('%').parse::<u32>() {
+                Ok(percent) if percent <= 100 => Ok(SeasonSetting::KeptPercent(percent)),
+                _ => Err("The percentage must be a whole number from 0 to 100.".to_string()),
+            },
+            _ => Err(format!(
+                "Unknown setting. Try one of: `{}`.",
+                Self::NAMES.join("`, `")
+            )),
+        }
+    }
+}
+
+impl Display for SeasonSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeasonSetting::LengthDays(0) => write!(f, "`length`: until ended by an admin"),
+            SeasonSetting::LengthDays(days) => write!(f, "`length`: {days} days"),
+            SeasonSetting::KeptPercent(0) => write!(f, "`keep`: 0%, the exp is reset"),
+            SeasonSetting::KeptPercent(percent) => write!(f, "`keep`: {percent}% of the exp"),
+        }
+    }
+}
+
+impl SeasonSettings {
+    pub(crate) fn apply(&mut self, setting: SeasonSetting) {
+        match setting {
+            SeasonSetting::LengthDays(days) => self.length_days = days,
+            SeasonSetting::KeptPercent(percent) => self.kept_percent = percent,
+        }
+    }
+
+    /// Returns the current values of all settings.
+    pub(crate) fn to_settings(self) -> Vec<SeasonSetting> {
+        vec![
+            SeasonSetting::LengthDays(self.length_days),
+            SeasonSetting::KeptPercent(self.kept_percent),
+        ]
+    }
+
+    /// Returns the exp that a member keeps into the next season.
+    ///
+    /// It's rounded down the same way as in the database.
+    pub(crate) fn kept_exp(self, exp: Exp) -> Exp {
+        let Exp(exp) = exp;
+        // Widened so that the product can't overflow; the result is at most `exp` again
+        let kept: u128 = u128::from(exp) * u128::from(self.kept_percent) / 100;
+        Exp(u64::try_from(kept).unwrap_or(u64::MAX))
+    }
+}
+
+impl From<dao::SeasonSettings> for SeasonSettings {
+    fn from(dao: dao::SeasonSettings) -> Self {
+        let dao::SeasonSettings {
+            length_days,
+            kept_percent,
+        } = dao;
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let (length_days, kept_percent) = (length_days as u32, kept_percent as u32);
+        Self {
+            length_days,
+            kept_percent,
+        }
+    }
+}
+
+/// Carries the exp of the members over into the next season.
+///
+/// The earned roles are not relocated here since the Discord roles have to be
+/// reconciled with the new exp anyway.
+pub(super) fn carry_over(users: &mut [ServerMember], settings: SeasonSettings) {
+    for sm in users {
+        sm.exp = settings.kept_exp(sm.exp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kept_exp_is_rounded_down() {
+        let settings = SeasonSettings {
+            length_days: 0,
+            kept_percent: 50,
+        };
+        assert_eq!(settings.kept_exp(Exp(7)).0, 3);
+    }
+
+    #[test]
+    fn kept_exp_does_not_overflow() {
+        let settings = SeasonSettings {
+            length_days: 0,
+            kept_percent: 100,
+        };
+        assert_eq!(settings.kept_exp(Exp(u64::MAX)).0, u64::MAX);
+    }
+}
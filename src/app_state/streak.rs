@@ -0,0 +1,41 @@
+//! Daily activity streaks, the runs of consecutive days on which a member
+//! posted at least one rewarded message.
+
+use std::collections::BTreeMap;
+
+use super::exp::Exp;
+use crate::db::dao;
+
+/// Runtime-configurable settings of the activity streaks.
+#[derive(Debug, Clone)]
+pub(crate) struct StreakSettings {
+    /// The IANA name of the timezone in which the days of the streaks start and end.
+    pub(crate) timezone: String,
+    /// The bonus exp for reaching a streak of the given number of days.
+    pub(crate) bonuses: BTreeMap<u32, Exp>,
+}
+
+/// A day added to the streak of a member.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreakDay {
+    /// The length of the streak including the added day.
+    pub(crate) days: u32,
+    /// The bonus exp awarded for reaching the milestone, if the streak has reached one.
+    pub(crate) bonus: Option<Exp>,
+}
+
+impl StreakSettings {
+    pub(crate) fn new(timezone: String, bonuses: Vec<dao::StreakBonus>) -> Self {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let bonuses: BTreeMap<u32, Exp> = bonuses
+            .into_iter()
+            .map(|dao::StreakBonus { days, bonus_exp }| (days as u32, Exp::from_i64(bonus_exp)))
+            .collect();
+        Self { timezone, bonuses }
+    }
+
+    /// Returns the bonus exp for reaching a streak of `days` days, if it's a milestone.
+    pub(crate) fn bonus_for(&self, days: u32) -> Option<Exp> {
+        self.bonuses.get(&days).copied()
+    }
+}
@@ -1,32 +1,57 @@
-use super::{exp::Exp, in_cache, EarnedRole, ServerMember};
+use super::{
+    event::{EventAnnouncement, ExpEvent, ExpEvents},
+    exp::{Exp, ExpAdjustment, ExpChange, ExpSource},
+    exp_rules::{ExpMultipliers, ExpSetting, ExpSettings},
+    in_cache,
+    ladder::{self, EarnedRoleChange, EarnedRoleMode},
+    level_up::{LevelUp, LevelUpAnnouncement, LevelUpSetting, LevelUpSettings},
+    roles::{Presentation, SelfRoleEntry, SelfRoleMsgs},
+    season::{self, SeasonSetting, SeasonSettings},
+    streak::{StreakDay, StreakSettings},
+    EarnedRole, ServerMember,
+};
 use serenity::{
-    http::Http,
-    model::prelude::{Member, RoleId, UserId},
+    http::{Http, StatusCode},
+    model::prelude::{ChannelId, Member, MessageId, ReactionType, Role, RoleId, UserId},
 };
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 
 use super::AppState;
 use crate::db;
 use crate::immut_data::dynamic::BotCfg;
+use crate::rank_card::theme::{Theme, ThemeSetting};
 
 /// "Synchronized" way of adding experience points to a user.
 ///
 /// "Synchronized" means that it updates both the database and the cache.
+/// Every change is recorded separately in the exp ledger, and the `adjustment`
+/// of a moderator also in the audit trail.
+/// The user is promoted or demoted to the earned role that the new exp is enough for,
+/// skipping any number of tiers. The announcement of a promotion is returned so that
+/// it can be posted once the app state is unlocked.
 pub(crate) async fn add_signed_exp(
     http: &Http,
     cfg: &BotCfg,
     app_state: &mut AppState,
     pool: &PgPool,
     member: &Member,
-    delta: i64,
-) -> crate::util::Result<Exp> {
+    changes: &[ExpChange],
+    adjustment: Option<ExpAdjustment<'_>>,
+) -> crate::util::Result<(Exp, Option<LevelUpAnnouncement>)> {
     let discord_id: UserId = member.user.id;
-    let db_exp: Exp = db::add_signed_exp(pool, discord_id, delta).await?;
+    let db_exp: Exp = db::add_signed_exp(pool, discord_id, changes, adjustment).await?;
+    let delta: i64 = changes
+        .iter()
+        .fold(0, |total, change| total.saturating_add(change.delta));
     let in_cache_exp = if let Some(exp) = in_cache::add_signed_exp(app_state, discord_id, delta) {
         exp
     } else {
-        eprintln!("Couldn't find the user in the cache");
-        Exp::from_i64(delta)
+        // The member joined after the app state was loaded. They are cached with the exp
+        // from before the change so that the earned roles below are updated as usual.
+        in_cache::add_member(app_state, discord_id, db_exp.to_i64() - delta);
+        in_cache::add_signed_exp(app_state, discord_id, delta)
+            .expect("The member was just added to the cache")
     };
 
     if db_exp != in_cache_exp {
@@ -43,62 +68,75 @@ pub(crate) async fn add_signed_exp(
         panic!("Couldn't find the user in the cache");
     };
 
-    let Some(exp_milestone) = user.nxt_exp_milestone else {
-        return Ok(db_exp);
-    };
-
-    if user.exp >= exp_milestone {
-        let next_earned_role_idx = match user.earned_role_idx {
-            Some(idx) => {
-                let old_role = match app_state.sorted_earned_roles.get(idx) {
-                    Some(r) => r.role_id,
-                    None => unreachable!("The user has an invalid earned_role_idx"),
-                };
-                http.remove_member_role(
-                    cfg.discord_server_id.0,
-                    user.discord_id.0,
-                    old_role.0,
-                    None,
-                )
-                .await?;
-                idx + 1
-            }
-            None => 0,
-        };
-        let next_earned_role_id = match app_state.sorted_earned_roles.get(next_earned_role_idx) {
-            Some(r) => r.role_id,
-            None => unreachable!("The user has an invalid earned_role_idx"),
-        };
-        user.earned_role_idx = Some(next_earned_role_idx);
-        user.nxt_exp_milestone = app_state
-            .sorted_earned_roles
-            .get(next_earned_role_idx)
-            .map(|r| r.exp_needed);
-        http.add_member_role(
-            cfg.discord_server_id.0,
-            user.discord_id.0,
-            next_earned_role_id.0,
-            None,
-        )
-        .await?;
+    // A big enough delta can cross several thresholds at once, in either direction.
+    let (earned_role_idx, nxt_exp_milestone) =
+        super::locate_on_ladder(user.exp, &app_state.sorted_earned_roles);
+    user.nxt_exp_milestone = nxt_exp_milestone;
+    if earned_role_idx == user.earned_role_idx {
+        return Ok((db_exp, None));
+    }
+    let mode: EarnedRoleMode = app_state.earned_role_mode;
+    let old_roles: Vec<RoleId> =
+        ladder::deserved_roles(&app_state.sorted_earned_roles, user.earned_role_idx, mode);
+    let new_roles: Vec<RoleId> =
+        ladder::deserved_roles(&app_state.sorted_earned_roles, earned_role_idx, mode);
+    let is_promotion: bool = earned_role_idx > user.earned_role_idx;
+    user.earned_role_idx = earned_role_idx;
+    for old_role in old_roles.iter().filter(|r| !new_roles.contains(r)) {
+        http.remove_member_role(cfg.discord_server_id.0, discord_id.0, old_role.0, None)
+            .await?;
+    }
+    for new_role in new_roles.iter().filter(|r| !old_roles.contains(r)) {
+        http.add_member_role(cfg.discord_server_id.0, discord_id.0, new_role.0, None)
+            .await?;
     }
 
-    Ok(db_exp)
+    let reached_role = earned_role_idx.and_then(|idx| app_state.sorted_earned_roles.get(idx));
+    let announcement: Option<LevelUpAnnouncement> = match (is_promotion, reached_role) {
+        (true, Some(reached_role)) => {
+            let level_up = LevelUp {
+                user_id: discord_id,
+                role_id: reached_role.role_id,
+                exp: user.exp,
+                next_milestone: nxt_exp_milestone,
+            };
+            let ping: bool = !app_state.level_up_ping_opt_outs.contains(&discord_id);
+            app_state
+                .level_up_settings
+                .announcement(cfg.discord_bot_channel, &level_up, ping)
+        }
+        _ => None,
+    };
+
+    Ok((db_exp, announcement))
 }
 
+/// "Synchronized" way of adding an earned role to the ladder.
+///
+/// The members are promoted or demoted according to the new ladder. The returned changes
+/// of their Discord roles are made with [`apply_earned_role_changes`].
 pub(crate) async fn add_earned_role(
-    http: &Http,
-    cfg: &BotCfg,
     sorted_earned_roles: &mut Vec<EarnedRole>,
     users: &mut [ServerMember],
+    mode: EarnedRoleMode,
     pool: &PgPool,
-    role_id: RoleId,
-    exp_needed: Exp,
-) -> crate::util::Result<()> {
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+    earned_role: EarnedRole,
+) -> crate::util::Result<Vec<EarnedRoleChange>> {
+    let EarnedRole {
+        role_id,
+        exp_needed,
+    } = earned_role;
+    if let Some(role_id) =
+        super::role_with_threshold(sorted_earned_roles, exp_needed, Some(role_id))
+    {
+        return Err(crate::util::Error::TakenExpThreshold {
+            role_id,
+            exp_needed: exp_needed.0,
+        });
+    }
     db::add_earned_role(pool, role_id, exp_needed).await?;
-    let pos = sorted_earned_roles
-        .binary_search_by_key(&exp_needed, |r| r.exp_needed)
-        .expect_err("The role with the same exp_needed already exists");
+    let pos = sorted_earned_roles.partition_point(|r| r.exp_needed < exp_needed);
     sorted_earned_roles.insert(
         pos,
         EarnedRole {
@@ -106,77 +144,640 @@ pub(crate) async fn add_earned_role(
             exp_needed,
         },
     );
-    let sm_iter = users.iter_mut();
-    if sorted_earned_roles.len() == 1 {
-        for sm in sm_iter {
-            if sm.exp > exp_needed {
-                sm.earned_role_idx = Some(0);
-                sm.nxt_exp_milestone = None;
-                http.add_member_role(cfg.discord_server_id.0, sm.discord_id.0, role_id.0, None)
-                    .await?;
-            } else {
-                sm.earned_role_idx = None;
-                sm.nxt_exp_milestone = Some(exp_needed);
+    Ok(reconcile_earned_roles(
+        sorted_earned_roles,
+        users,
+        member_roles,
+        &[],
+        mode,
+        false,
+    ))
+}
+
+/// "Synchronized" way of changing the exp needed for an earned role.
+///
+/// The members are promoted or demoted according to the new ladder. The returned changes
+/// of their Discord roles are made with [`apply_earned_role_changes`].
+pub(crate) async fn set_earned_role_exp(
+    sorted_earned_roles: &mut [EarnedRole],
+    users: &mut [ServerMember],
+    mode: EarnedRoleMode,
+    pool: &PgPool,
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+    earned_role: EarnedRole,
+) -> crate::util::Result<Vec<EarnedRoleChange>> {
+    let EarnedRole {
+        role_id,
+        exp_needed,
+    } = earned_role;
+    if let Some(role_id) =
+        super::role_with_threshold(sorted_earned_roles, exp_needed, Some(role_id))
+    {
+        return Err(crate::util::Error::TakenExpThreshold {
+            role_id,
+            exp_needed: exp_needed.0,
+        });
+    }
+    db::add_earned_role(pool, role_id, exp_needed).await?;
+    if let Some(earned_role) = sorted_earned_roles
+        .iter_mut()
+        .find(|earned_role| earned_role.role_id == role_id)
+    {
+        earned_role.exp_needed = exp_needed;
+    }
+    sorted_earned_roles.sort_by_key(|earned_role| earned_role.exp_needed);
+    Ok(reconcile_earned_roles(
+        sorted_earned_roles,
+        users,
+        member_roles,
+        &[],
+        mode,
+        false,
+    ))
+}
+
+/// "Synchronized" way of removing an earned role from the ladder.
+///
+/// The Discord role itself is kept, but the members lose it and get the earned role
+/// below it instead. The returned changes of their Discord roles are made with
+/// [`apply_earned_role_changes`].
+pub(crate) async fn remove_earned_role(
+    sorted_earned_roles: &mut Vec<EarnedRole>,
+    users: &mut [ServerMember],
+    mode: EarnedRoleMode,
+    pool: &PgPool,
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+    role_id: RoleId,
+) -> crate::util::Result<Vec<EarnedRoleChange>> {
+    db::remove_earned_role(pool, role_id).await?;
+    sorted_earned_roles.retain(|earned_role| earned_role.role_id != role_id);
+    Ok(reconcile_earned_roles(
+        sorted_earned_roles,
+        users,
+        member_roles,
+        &[role_id],
+        mode,
+        false,
+    ))
+}
+
+/// Fetches the roles of all members of the server who are not bots.
+pub(crate) async fn fetch_member_roles(
+    http: &Http,
+    cfg: &BotCfg,
+) -> crate::util::Result<HashMap<UserId, Vec<RoleId>>> {
+    const MEMBERS_LIMIT: u64 = 1000;
+
+    let mut member_roles = HashMap::<UserId, Vec<RoleId>>::new();
+    let mut after: Option<UserId> = None;
+    loop {
+        let page: Vec<Member> = cfg
+            .discord_server_id
+            .members(http, Some(MEMBERS_LIMIT), after)
+            .await?;
+        after = page.last().map(|m| m.user.id);
+        let is_last_page = page.len() < usize::try_from(MEMBERS_LIMIT).unwrap_or(usize::MAX);
+        member_roles.extend(
+            page.into_iter()
+                .filter(|m| !m.user.bot)
+                .map(|m| (m.user.id, m.roles)),
+        );
+        if is_last_page {
+            break;
+        }
+    }
+    Ok(member_roles)
+}
+
+/// Plans how to bring the earned roles of every member in line with the ladder of earned roles.
+///
+/// The cached position of every member on the ladder is recomputed, and `member_roles`,
+/// the actual Discord roles of the members, are compared against it. Only the roles that
+/// differ are to be added or removed. With `dry_run`, the cache isn't changed either.
+///
+/// `retired_roles` are the roles that were removed from the ladder and should be taken away.
+pub(crate) fn reconcile_earned_roles(
+    sorted_earned_roles: &[EarnedRole],
+    users: &mut [ServerMember],
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+    retired_roles: &[RoleId],
+    mode: EarnedRoleMode,
+    dry_run: bool,
+) -> Vec<EarnedRoleChange> {
+    if !dry_run {
+        ladder::relocate_members(sorted_earned_roles, users);
+    }
+    ladder::plan_earned_roles(
+        sorted_earned_roles,
+        users,
+        member_roles,
+        retired_roles,
+        mode,
+    )
+}
+
+/// Makes the changes of the earned roles planned by [`reconcile_earned_roles`].
+///
+/// Every change is a request to Discord, so the app state shouldn't be locked meanwhile.
+/// The failures to change the roles of a member are logged and don't stop the other changes.
+pub(crate) async fn apply_earned_role_changes(
+    http: &Http,
+    cfg: &BotCfg,
+    changes: &[EarnedRoleChange],
+) {
+    for EarnedRoleChange {
+        user_id,
+        add,
+        remove,
+    } in changes
+    {
+        for role_id in remove {
+            if let Err(e) = http
+                .remove_member_role(cfg.discord_server_id.0, user_id.0, role_id.0, None)
+                .await
+            {
+                eprintln!("Failed to remove the earned role {role_id} from {user_id}: {e}");
             }
         }
-    } else if sorted_earned_roles.len() - 1 == pos {
-        let old_last_idx = pos - 1;
-        for sm in sm_iter {
-            let Some(old_earned_role_idx) = sm.earned_role_idx else {
-                continue;
+        for role_id in add {
+            if let Err(e) = http
+                .add_member_role(cfg.discord_server_id.0, user_id.0, role_id.0, None)
+                .await
+            {
+                eprintln!("Failed to give the earned role {role_id} to {user_id}: {e}");
+            }
+        }
+    }
+}
+
+/// "Synchronized" way of switching between keeping and replacing the reached earned roles.
+///
+/// The returned changes that reconcile the roles of all members with the new mode
+/// are made with [`apply_earned_role_changes`].
+pub(crate) async fn set_earned_role_mode(
+    app_state: &mut AppState,
+    pool: &PgPool,
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+    mode: EarnedRoleMode,
+) -> crate::util::Result<Vec<EarnedRoleChange>> {
+    db::set_earned_role_mode(pool, mode).await?;
+    app_state.earned_role_mode = mode;
+    Ok(reconcile_earned_roles(
+        &app_state.sorted_earned_roles,
+        &mut app_state.users,
+        member_roles,
+        &[],
+        mode,
+        false,
+    ))
+}
+
+/// "Synchronized" way of changing one of the season settings.
+pub(crate) async fn set_season_setting(
+    season_settings: &mut SeasonSettings,
+    pool: &PgPool,
+    setting: SeasonSetting,
+) -> Result<(), sqlx::Error> {
+    db::set_season_setting(pool, setting).await?;
+    season_settings.apply(setting);
+    Ok(())
+}
+
+/// "Synchronized" way of ending the current season.
+///
+/// The standings are archived, the members keep the configured part of their exp
+/// and the changes that reconcile their earned roles with it are returned along with
+/// the number of the archived season. They are made with [`apply_earned_role_changes`].
+pub(crate) async fn end_season(
+    app_state: &mut AppState,
+    pool: &PgPool,
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+) -> crate::util::Result<(i64, Vec<EarnedRoleChange>)> {
+    let number: i64 = db::end_season(pool, app_state.season_settings.kept_percent).await?;
+    season::carry_over(&mut app_state.users, app_state.season_settings);
+    let changes = reconcile_earned_roles(
+        &app_state.sorted_earned_roles,
+        &mut app_state.users,
+        member_roles,
+        &[],
+        app_state.earned_role_mode,
+        false,
+    );
+    Ok((number, changes))
+}
+
+/// "Synchronized" way of changing the timezone in which the days of the streaks are counted.
+pub(crate) async fn set_streak_timezone(
+    streak_settings: &mut StreakSettings,
+    pool: &PgPool,
+    timezone: String,
+) -> Result<(), sqlx::Error> {
+    db::set_streak_timezone(pool, &timezone).await?;
+    streak_settings.timezone = timezone;
+    Ok(())
+}
+
+/// "Synchronized" way of setting or removing (with `None`) the bonus for a streak milestone.
+pub(crate) async fn set_streak_bonus(
+    streak_settings: &mut StreakSettings,
+    pool: &PgPool,
+    days: u32,
+    bonus: Option<Exp>,
+) -> Result<(), sqlx::Error> {
+    db::set_streak_bonus(pool, days, bonus).await?;
+    match bonus {
+        Some(bonus) => streak_settings.bonuses.insert(days, bonus),
+        None => streak_settings.bonuses.remove(&days),
+    };
+    Ok(())
+}
+
+/// "Synchronized" way of adding the current day to the activity streak of the member.
+///
+/// If the streak reaches a milestone, the member is awarded the bonus exp for it, which
+/// can promote them like any other exp. The day is `None` if the current day is already
+/// a part of the streak.
+pub(crate) async fn record_streak_day(
+    http: &Http,
+    cfg: &BotCfg,
+    app_state: &mut AppState,
+    pool: &PgPool,
+    member: &Member,
+) -> crate::util::Result<(Option<StreakDay>, Option<LevelUpAnnouncement>)> {
+    let timezone: &str = &app_state.streak_settings.timezone;
+    let Some(days) = db::record_streak_day(pool, member.user.id, timezone).await? else {
+        return Ok((None, None));
+    };
+    let days = u32::try_from(days).unwrap_or(u32::MAX);
+    let bonus: Option<Exp> = app_state.streak_settings.bonus_for(days);
+    let mut announcement: Option<LevelUpAnnouncement> = None;
+    if let Some(bonus) = bonus {
+        let change = ExpChange {
+            delta: bonus.to_i64(),
+            source: ExpSource::Streak,
+            channel_id: None,
+        };
+        (_, announcement) =
+            add_signed_exp(http, cfg, app_state, pool, member, &[change], None).await?;
+    }
+    Ok((Some(StreakDay { days, bonus }), announcement))
+}
+
+/// "Synchronized" way of scheduling an exp event. Returns the scheduled event.
+pub(crate) async fn add_exp_event(
+    exp_events: &mut ExpEvents,
+    pool: &PgPool,
+    created_by: UserId,
+    multiplier: f64,
+    starts_at: i64,
+    ends_at: i64,
+    channels: Vec<ChannelId>,
+) -> Result<ExpEvent, sqlx::Error> {
+    let id: i64 =
+        db::add_exp_event(pool, created_by, multiplier, starts_at, ends_at, &channels).await?;
+    let event = ExpEvent {
+        id,
+        multiplier,
+        starts_at,
+        ends_at,
+        channels,
+        start_announced: false,
+    };
+    exp_events.insert(event.clone());
+    Ok(event)
+}
+
+/// "Synchronized" way of cancelling an exp event that hasn't ended yet.
+///
+/// Returns the cancelled event, or `None` if there is no such event.
+pub(crate) async fn cancel_exp_event(
+    exp_events: &mut ExpEvents,
+    pool: &PgPool,
+    id: i64,
+) -> Result<Option<ExpEvent>, sqlx::Error> {
+    if !exp_events.events().iter().any(|event| event.id == id) {
+        return Ok(None);
+    }
+    db::remove_exp_event(pool, id).await?;
+    Ok(exp_events.remove(id))
+}
+
+/// "Synchronized" way of taking the starts and the ends of the exp events
+/// that are due to be announced.
+///
+/// The announcements are recorded before they are made, so that they are
+/// never made twice, even if the bot restarts.
+pub(crate) async fn take_event_announcements(
+    exp_events: &mut ExpEvents,
+    pool: &PgPool,
+    now: i64,
+) -> Result<Vec<EventAnnouncement>, sqlx::Error> {
+    let announcements: Vec<EventAnnouncement> = exp_events.due_announcements(now);
+    for announcement in &announcements {
+        db::mark_exp_event_announced(pool, announcement).await?;
+        exp_events.mark_announced(announcement);
+    }
+    Ok(announcements)
+}
+
+/// "Synchronized" way of changing one of the level-up announcement settings.
+pub(crate) async fn set_level_up_setting(
+    level_up_settings: &mut LevelUpSettings,
+    pool: &PgPool,
+    setting: LevelUpSetting,
+) -> Result<(), sqlx::Error> {
+    db::set_level_up_setting(pool, &setting).await?;
+    level_up_settings.apply(setting);
+    Ok(())
+}
+
+/// "Synchronized" way of letting a member opt in or out of the level-up pings.
+pub(crate) async fn set_level_up_pings(
+    level_up_ping_opt_outs: &mut HashSet<UserId>,
+    pool: &PgPool,
+    discord_id: UserId,
+    pings: bool,
+) -> Result<(), sqlx::Error> {
+    db::set_level_up_pings(pool, discord_id, pings).await?;
+    if pings {
+        level_up_ping_opt_outs.remove(&discord_id);
+    } else {
+        level_up_ping_opt_outs.insert(discord_id);
+    }
+    Ok(())
+}
+
+/// "Synchronized" way of changing one of the experience settings.
+pub(crate) async fn set_exp_setting(
+    exp_settings: &mut ExpSettings,
+    pool: &PgPool,
+    setting: ExpSetting,
+) -> Result<(), sqlx::Error> {
+    db::set_exp_setting(pool, setting).await?;
+    exp_settings.apply(setting);
+    Ok(())
+}
+
+/// "Synchronized" way of changing one of the properties of the rank card theme.
+pub(crate) async fn set_theme_setting(
+    theme: &mut Theme,
+    pool: &PgPool,
+    setting: ThemeSetting,
+) -> Result<(), sqlx::Error> {
+    db::set_theme_setting(pool, setting).await?;
+    theme.apply(setting);
+    Ok(())
+}
+
+/// "Synchronized" way of setting the exp multiplier of a channel.
+///
+/// `None` removes the multiplier, which is the same as setting it to 1.
+pub(crate) async fn set_channel_exp_multiplier(
+    exp_multipliers: &mut ExpMultipliers,
+    pool: &PgPool,
+    channel_id: ChannelId,
+    multiplier: Option<f64>,
+) -> Result<(), sqlx::Error> {
+    match multiplier {
+        Some(multiplier) => {
+            db::set_channel_exp_multiplier(pool, channel_id, multiplier).await?;
+            exp_multipliers.channels.insert(channel_id, multiplier);
+        }
+        None => {
+            db::remove_channel_exp_multiplier(pool, channel_id).await?;
+            exp_multipliers.channels.remove(&channel_id);
+        }
+    }
+    Ok(())
+}
+
+/// "Synchronized" way of setting the exp multiplier of a role.
+///
+/// `None` removes the multiplier, which is the same as setting it to 1.
+pub(crate) async fn set_role_exp_multiplier(
+    exp_multipliers: &mut ExpMultipliers,
+    pool: &PgPool,
+    role_id: RoleId,
+    multiplier: Option<f64>,
+) -> Result<(), sqlx::Error> {
+    match multiplier {
+        Some(multiplier) => {
+            db::set_role_exp_multiplier(pool, role_id, multiplier).await?;
+            exp_multipliers.roles.insert(role_id, multiplier);
+        }
+        None => {
+            db::remove_role_exp_multiplier(pool, role_id).await?;
+            exp_multipliers.roles.remove(&role_id);
+        }
+    }
+    Ok(())
+}
+
+/// Reloads the self-role messages from the database so that the cache
+/// mirrors the `self_assigned_roles` table.
+async fn reload_self_role_msgs(
+    self_role_msgs: &mut SelfRoleMsgs,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let (reloaded, invalid_rows) = SelfRoleMsgs::load(db::sorted_self_assigned_roles(pool).await?);
+    for problem in invalid_rows {
+        eprintln!("{problem}");
+    }
+    *self_role_msgs = reloaded;
+    Ok(())
+}
+
+/// "Synchronized" way of making a role self-assignable via a self-role message.
+pub(crate) async fn add_self_role(
+    self_role_msgs: &mut SelfRoleMsgs,
+    pool: &PgPool,
+    excl_role_group_id: i64,
+    role_id: RoleId,
+    message_id: MessageId,
+    emoji: &ReactionType,
+    presentation: Presentation,
+) -> Result<(), sqlx::Error> {
+    db::add_self_assigned_role(
+        pool,
+        excl_role_group_id,
+        role_id,
+        message_id,
+        emoji,
+        presentation,
+    )
+    .await?;
+    reload_self_role_msgs(self_role_msgs, pool).await
+}
+
+/// "Synchronized" way of making a role no longer self-assignable.
+pub(crate) async fn remove_self_role(
+    self_role_msgs: &mut SelfRoleMsgs,
+    pool: &PgPool,
+    role_id: RoleId,
+) -> Result<(), sqlx::Error> {
+    db::remove_self_assigned_role(pool, role_id).await?;
+    reload_self_role_msgs(self_role_msgs, pool).await
+}
+
+/// "Synchronized" way of moving a self-assigned role to another group of mutually exclusive roles.
+pub(crate) async fn set_excl_role_group(
+    self_role_msgs: &mut SelfRoleMsgs,
+    pool: &PgPool,
+    role_id: RoleId,
+    excl_role_group_id: i64,
+) -> Result<(), sqlx::Error> {
+    db::set_excl_role_group(pool, role_id, excl_role_group_id).await?;
+    reload_self_role_msgs(self_role_msgs, pool).await
+}
+
+/// "Synchronized" way of changing the way a self-role message offers its roles.
+pub(crate) async fn set_self_role_msg_presentation(
+    self_role_msgs: &mut SelfRoleMsgs,
+    pool: &PgPool,
+    message_id: MessageId,
+    presentation: Presentation,
+) -> Result<(), sqlx::Error> {
+    db::set_self_role_msg_presentation(pool, message_id, presentation).await?;
+    reload_self_role_msgs(self_role_msgs, pool).await
+}
+
+/// "Synchronized" way of forgetting about a self-role message and all of its roles.
+pub(crate) async fn remove_self_role_msg(
+    self_role_msgs: &mut SelfRoleMsgs,
+    pool: &PgPool,
+    message_id: MessageId,
+) -> Result<(), sqlx::Error> {
+    db::remove_self_role_msg(pool, message_id).await?;
+    reload_self_role_msgs(self_role_msgs, pool).await
+}
+
+/// Grants and revokes self-assigned roles so that they match the reactions
+/// on the self-role messages.
+///
+/// Reactions that were added or removed while the bot was offline
+/// are not delivered as events, so this reconciliation is meant to be run on startup.
+/// `entries` are the entries of the self-role messages, taken out of the app state
+/// so that it isn't locked while Discord is queried.
+///
+/// Of the mutually exclusive roles that a member reacted for, the member keeps the one
+/// they already hold, or else gets the first one. The others are taken away,
+/// just like the roles that the member no longer reacts for.
+///
+/// Returns the human-readable descriptions of the tracked messages and roles
+/// that no longer exist on the server.
+pub(crate) async fn reconcile_self_roles(
+    http: &Http,
+    cfg: &BotCfg,
+    entries: Vec<SelfRoleEntry>,
+    member_roles: &HashMap<UserId, Vec<RoleId>>,
+) -> crate::util::Result<Vec<String>> {
+    const REACTION_USERS_LIMIT: u8 = 100;
+
+    let mut problems = Vec::<String>::new();
+    let guild_roles: HashMap<RoleId, Role> = cfg.discord_server_id.roles(http).await?;
+
+    let mut missing_message_id: Option<MessageId> = None;
+    let mut reactors_by_role = Vec::<(RoleId, i64, HashSet<UserId>)>::new();
+    for SelfRoleEntry {
+        message_id,
+        role_id,
+        reaction,
+        excl_role_group_id,
+        presentation,
+    } in entries
+    {
+        // Roles offered via message components are granted and revoked via interactions,
+        // which are not lost while the bot is offline.
+        if presentation != Presentation::Reactions || missing_message_id == Some(message_id) {
+            continue;
+        }
+        if !guild_roles.contains_key(&role_id) {
+            problems.push(format!(
+                "The role `{role_id}` self-assigned via {reaction} \
+                on the message `{message_id}` no longer exists."
+            ));
+            continue;
+        }
+
+        let mut reactors = HashSet::<UserId>::new();
+        let mut after: Option<u64> = None;
+        loop {
+            let page = match http
+                .get_reaction_users(
+                    cfg.discord_self_role_channel.0,
+                    message_id.0,
+                    &reaction,
+                    REACTION_USERS_LIMIT,
+                    after,
+                )
+                .await
+            {
+                Ok(page) => page,
+                Err(serenity::Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+                    // Either the message or the emoji is gone.
+                    if cfg
+                        .discord_self_role_channel
+                        .message(http, message_id)
+                        .await
+                        .is_err()
+                    {
+                        problems.push(format!(
+                            "The self-role message `{message_id}` no longer exists."
+                        ));
+                        missing_message_id = Some(message_id);
+                    } else {
+                        problems.push(format!(
+                            "The emoji {reaction} for the role `{role_id}` \
+                            can't be found on the message `{message_id}`."
+                        ));
+                    }
+                    break;
+                }
+                Err(e) => return Err(e.into()),
             };
-            if old_earned_role_idx != old_last_idx {
+            after = page.last().map(|user| user.id.0);
+            let is_last_page = page.len() < usize::from(REACTION_USERS_LIMIT);
+            reactors.extend(page.into_iter().filter(|u| !u.bot).map(|u| u.id));
+            if is_last_page {
+                break;
+            }
+        }
+        if missing_message_id == Some(message_id) {
+            continue;
+        }
+        reactors_by_role.push((role_id, excl_role_group_id, reactors));
+    }
+
+    for (user_id, roles) in member_roles {
+        // The role that the member should have from each group of mutually exclusive roles
+        let mut chosen = HashMap::<i64, RoleId>::new();
+        for (role_id, excl_role_group_id, reactors) in &reactors_by_role {
+            if !reactors.contains(user_id) {
                 continue;
             }
-            if sm.exp < exp_needed {
-                sm.nxt_exp_milestone = Some(exp_needed);
-            } else {
-                sm.earned_role_idx = Some(pos - 1);
-                sm.nxt_exp_milestone = None;
-                let old_role_id = match sorted_earned_roles.get(old_earned_role_idx) {
-                    Some(r) => r.role_id,
-                    None => unreachable!("The user has an invalid earned_role_idx"),
-                };
-                http.remove_member_role(
-                    cfg.discord_server_id.0,
-                    sm.discord_id.0,
-                    old_role_id.0,
-                    None,
-                )
-                .await?;
-                http.add_member_role(cfg.discord_server_id.0, sm.discord_id.0, role_id.0, None)
-                    .await?;
+            let chosen_id: &mut RoleId = chosen.entry(*excl_role_group_id).or_insert(*role_id);
+            if roles.contains(role_id) && !roles.contains(chosen_id) {
+                *chosen_id = *role_id;
             }
         }
-    } else {
-        for ServerMember {
-            ref discord_id,
-            earned_role_idx,
-            nxt_exp_milestone,
-            ref exp,
-            ..
-        } in sm_iter
-        {
-            match (earned_role_idx, nxt_exp_milestone) {
-                (Some(idx), _m) if *idx > pos => {
-                    *idx += 1;
-                    // the milestone should be the same
-                }
-                (Some(idx), m) if *idx == pos && *exp < exp_needed => {
-                    *m = Some(exp_needed);
+        for (role_id, excl_role_group_id, _reactors) in &reactors_by_role {
+            let is_chosen = chosen.get(excl_role_group_id) == Some(role_id);
+            let res = match (is_chosen, roles.contains(role_id)) {
+                (true, false) => {
+                    http.add_member_role(cfg.discord_server_id.0, user_id.0, role_id.0, None)
+                        .await
                 }
-                (Some(idx), _m) if *idx == pos && *exp >= exp_needed => {
-                    let old_role: RoleId = sorted_earned_roles[*idx].role_id;
-                    *idx += 1;
-                    let new_role: RoleId = sorted_earned_roles[*idx].role_id;
-                    http.add_member_role(cfg.discord_server_id.0, discord_id.0, new_role.0, None).await
-                        .unwrap_or_else(|e| panic!("Failed to give a role to a server member with discord_id={discord_id}: {e}"));
-                    http.remove_member_role(cfg.discord_server_id.0, discord_id.0, old_role.0, None).await
-                        .unwrap_or_else(|e| panic!("Failed to remove a role from a server member with discord_id: {discord_id}: {e}"));
+                (false, true) => {
+                    http.remove_member_role(cfg.discord_server_id.0, user_id.0, role_id.0, None)
+                        .await
                 }
-                _ => (),
+                _ => Ok(()),
+            };
+            if let Err(e) = res {
+                eprintln!("Failed to reconcile the self-assigned role {role_id} of {user_id}: {e}");
             }
         }
     }
-    Ok(())
+
+    Ok(problems)
 }
@@ -0,0 +1,96 @@
+//! Tracking of the time the members spend talking in voice channels.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use serenity::model::prelude::{ChannelId, UserId};
+
+/// A member connected to a voice channel, as seen in the voice states of the server.
+#[derive(Debug)]
+pub(crate) struct VoiceMember {
+    pub(crate) user_id: UserId,
+    pub(crate) channel_id: ChannelId,
+    pub(crate) is_bot: bool,
+    /// Whether the member is muted or deafened, by themselves or by a moderator.
+    pub(crate) is_muted: bool,
+}
+
+/// Whole minutes of voice activity that are due to be rewarded with experience points.
+#[derive(Debug)]
+pub(crate) struct VoiceReward {
+    pub(crate) user_id: UserId,
+    /// The channel the minutes were spent in.
+    pub(crate) channel_id: ChannelId,
+    pub(crate) minutes: u64,
+}
+
+#[derive(Debug)]
+struct VoiceSession {
+    channel_id: ChannelId,
+    /// The moment since which the time of the member counts, if it counts now.
+    eligible_since: Option<Instant>,
+    /// The time that counts but hasn't been rewarded yet.
+    banked: Duration,
+}
+
+/// The voice sessions of the members, from joining a voice channel to leaving it.
+///
+/// The sessions are kept only in memory. After a restart, the members who are still
+/// in voice channels start new sessions and the time Vampy was offline doesn't count.
+#[derive(Debug, Default)]
+pub(crate) struct VoiceActivity(HashMap<UserId, VoiceSession>);
+
+impl VoiceActivity {
+    /// Brings the sessions up to date with the current voice states of the server
+    /// and returns the whole minutes that are due to be rewarded.
+    ///
+    /// The time of a member counts only while they are neither muted nor deafened
+    /// and there is at least one more member (not a bot) in their channel.
+    /// The remainder of less than a minute is kept until the next refresh,
+    /// but it is lost when the member leaves the voice channel.
+    pub(crate) fn refresh(&mut self, voice_members: &[VoiceMember]) -> Vec<VoiceReward> {
+        let Self(sessions) = self;
+        let now = Instant::now();
+
+        let mut rewards: Vec<VoiceReward> = Vec::new();
+        for (user_id, session) in sessions.iter_mut() {
+            if let Some(eligible_since) = session.eligible_since.take() {
+                session.banked += now.duration_since(eligible_since);
+            }
+            let minutes = session.banked.as_secs() / 60;
+            if minutes > 0 {
+                session.banked -= Duration::from_secs(minutes * 60);
+                rewards.push(VoiceReward {
+                    user_id: *user_id,
+                    channel_id: session.channel_id,
+                    minutes,
+                });
+            }
+        }
+
+        let humans: Vec<&VoiceMember> = voice_members.iter().filter(|m| !m.is_bot).collect();
+        let present: HashSet<UserId> = humans.iter().map(|m| m.user_id).collect();
+        sessions.retain(|user_id, _session| present.contains(user_id));
+
+        let mut occupancy: HashMap<ChannelId, usize> = HashMap::new();
+        for m in &humans {
+            *occupancy.entry(m.channel_id).or_default() += 1;
+        }
+        for m in humans {
+            let session = sessions.entry(m.user_id).or_insert_with(|| VoiceSession {
+                channel_id: m.channel_id,
+                eligible_since: None,
+                banked: Duration::ZERO,
+            });
+            session.channel_id = m.channel_id;
+            let is_alone = occupancy.get(&m.channel_id).copied().unwrap_or_default() < 2;
+            if !m.is_muted && !is_alone {
+                session.eligible_since = Some(now);
+            }
+        }
+
+        rewards
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::atomic::AtomicBool;
+
+use serenity::{
+    model::prelude::{Member, UserId},
+    prelude::Context,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        exp::{Exp, ExpChange, ExpSource},
+        exp_rules::ExpSettings,
+        level_up::LevelUpAnnouncement,
+        rank::Rank,
+        sync,
+        type_map_keys::AppStateKey,
+        AppState,
+    },
+    db::{self, dao},
+    immut_data::{consts::DECAY_TICK, dynamic::BotCfg},
+};
+
+use super::{spawn_ticker, MainBot};
+
+static DECAY_TICKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+impl MainBot {
+    /// Spawns the task that regularly decays the exp of the inactive members.
+    pub(super) fn spawn_decay_ticker(&self, ctx: &Context) {
+        let ctx = ctx.clone();
+        let cfg = self.cfg.clone();
+        let pool = self.pool.clone();
+        spawn_ticker(&DECAY_TICKER_STARTED, DECAY_TICK, move || {
+            let (ctx, cfg, pool) = (ctx.clone(), cfg.clone(), pool.clone());
+            async move { decay_inactive_exp(&ctx, &cfg, &pool).await }
+        });
+    }
+}
+
+/// Takes away a part of the exp of every member that has been inactive for too long.
+///
+/// The exp of a member decays at most once per decay period. Members that lose
+/// their earned role are demoted the same way as when a moderator takes their exp.
+async fn decay_inactive_exp(ctx: &Context, cfg: &BotCfg, pool: &PgPool) {
+    let settings: ExpSettings = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        app_state.exp_settings.clone()
+    };
+    if settings.decay_after_days == 0 {
+        return;
+    }
+
+    let candidates: Vec<dao::ServerMember> = match db::decay_candidates(
+        pool,
+        settings.decay_after_days,
+        settings.decay_period_days,
+        Exp(settings.decay_floor),
+    )
+    .await
+    {
+        Ok(candidates) => candidates,
+        Err(e) => {
+            eprintln!("Failed to find the members whose exp should decay: {e}");
+            return;
+        }
+    };
+
+    let mut decayed_members: usize = 0;
+    let mut decayed_exp: u64 = 0;
+    for dao::ServerMember { discord_id, .. } in candidates {
+        #[allow(clippy::cast_sign_loss)]
+        let user_id = UserId(discord_id as u64);
+        let member: Member = match ctx.cache.member(cfg.discord_server_id, user_id) {
+            Some(member) => member,
+            None => match cfg.discord_server_id.member(&ctx.http, user_id).await {
+                Ok(member) => member,
+                Err(e) => {
+                    eprintln!("Failed to get member info for the exp decay of {user_id}: {e}");
+                    continue;
+                }
+            },
+        };
+
+        let announcement: Option<LevelUpAnnouncement> = {
+            let mut wlock = ctx.data.write().await;
+            let app_state: &mut AppState = wlock
+                .get_mut::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            // The exp might have changed since the candidates were selected
+            let Some(Rank { exp, .. }) = app_state.rank(user_id) else {
+                continue;
+            };
+            let Exp(decay) = settings.decay(exp);
+            if decay == 0 {
+                continue;
+            }
+            let change = ExpChange {
+                delta: -Exp(decay).to_i64(),
+                source: ExpSource::Decay,
+                channel_id: None,
+            };
+            match sync::add_signed_exp(&ctx.http, cfg, app_state, pool, &member, &[change], None)
+                .await
+            {
+                Ok((exp, announcement)) => {
+                    println!(
+                        "{}'s exp decayed by {decay} after inactivity: {exp:?}",
+                        member.user.name
+                    );
+                    decayed_members += 1;
+                    decayed_exp += decay;
+                    announcement
+                }
+                Err(e) => {
+                    eprintln!("Error during the exp decay of {user_id}: {e}");
+                    None
+                }
+            }
+        };
+        if let Some(announcement) = announcement {
+            announcement.post(&ctx.http).await;
+        }
+    }
+    if decayed_members > 0 {
+        println!(
+            "The exp of {decayed_members} inactive member(s) decayed by {decayed_exp} in total"
+        );
+    }
+}
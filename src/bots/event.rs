@@ -0,0 +1,63 @@
+use std::sync::atomic::AtomicBool;
+
+use serenity::prelude::Context;
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        event::{self, EventAnnouncement},
+        sync,
+        type_map_keys::AppStateKey,
+        AppState,
+    },
+    immut_data::{consts::EXP_EVENT_TICK, dynamic::BotCfg},
+};
+
+use super::{spawn_ticker, MainBot};
+
+static EXP_EVENT_TICKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+impl MainBot {
+    /// Spawns the task that announces the starts and the ends of the exp events.
+    pub(super) fn spawn_exp_event_ticker(&self, ctx: &Context) {
+        let ctx = ctx.clone();
+        let cfg = self.cfg.clone();
+        let pool = self.pool.clone();
+        spawn_ticker(&EXP_EVENT_TICKER_STARTED, EXP_EVENT_TICK, move || {
+            let (ctx, cfg, pool) = (ctx.clone(), cfg.clone(), pool.clone());
+            async move { announce_exp_events(&ctx, &cfg, &pool).await }
+        });
+    }
+}
+
+async fn announce_exp_events(ctx: &Context, cfg: &BotCfg, pool: &PgPool) {
+    let announcements: Vec<EventAnnouncement> = {
+        let mut wlock = ctx.data.write().await;
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let res =
+            sync::take_event_announcements(&mut app_state.exp_events, pool, event::unix_now())
+                .await;
+        match res {
+            Ok(announcements) => announcements,
+            Err(e) => {
+                eprintln!("Failed to take the exp event announcements: {e}");
+                return;
+            }
+        }
+    };
+
+    for announcement in announcements {
+        let content: String = announcement.content();
+        if let Err(e) = cfg
+            .discord_bot_channel
+            .send_message(&ctx.http, |m| {
+                m.content(&content).allowed_mentions(|am| am.empty_parse())
+            })
+            .await
+        {
+            eprintln!("Failed to announce the exp event: {e}");
+        }
+    }
+}
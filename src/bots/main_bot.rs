@@ -1,7 +1,15 @@
+use std::collections::HashMap;
+
 use serenity::{
     async_trait,
-    model::prelude::{Guild, Member, Message, PartialGuild, Ready},
+    model::{
+        application::interaction::Interaction,
+        prelude::{
+            Guild, Member, Message, PartialGuild, Reaction, Ready, RoleId, UserId, VoiceState,
+        },
+    },
     prelude::{Context, EventHandler, TypeMap},
+    utils::MessageBuilder,
 };
 use shuttle_secrets::SecretStore;
 use sqlx::{Executor, PgPool};
@@ -9,16 +17,23 @@ use tokio::sync::RwLockWriteGuard;
 
 use crate::{
     app_state::{
-        self,
-        exp::Exp,
+        self, event,
+        exp::{Exp, ExpChange, ExpSource},
+        level_up::LevelUpAnnouncement,
+        roles::SelfRoleEntry,
+        streak::StreakDay,
         type_map_keys::{AppStateKey, PgPoolKey},
         AppState,
     },
+    commands::leaderboard,
     immut_data::{consts::EXP_PER_MSG, dynamic::BotCfg},
     util::members,
 };
 
-use super::bot::{impl_bot, Bot};
+use super::{
+    bot::{impl_bot, Bot},
+    voice,
+};
 
 /// The bot structure that is used to
 ///
@@ -66,6 +81,10 @@ impl_bot!(MainBot);
 #[async_trait]
 impl EventHandler for MainBot {
     async fn ready(&self, ctx: Context, ready: Ready) {
+        /// The number of problems listed in the report. The rest are only counted
+        /// so that the report fits into a message.
+        const LISTED_PROBLEMS_LIMIT: usize = 12;
+
         let members = members(&ctx.http, self.discord_server_id()).await;
 
         let guild: PartialGuild = Guild::get(&ctx.http, self.discord_server_id()).await
@@ -73,12 +92,60 @@ impl EventHandler for MainBot {
 
         Self::print_server_members(&guild, &members);
 
-        let app_state = AppState::new(&self.pool, members).await;
+        let member_roles: HashMap<UserId, Vec<RoleId>> = members
+            .iter()
+            .filter(|m| !m.user.bot)
+            .map(|m| (m.user.id, m.roles.clone()))
+            .collect();
+
+        let (app_state, mut problems) = AppState::new(&self.pool, members).await;
+        for problem in &problems {
+            eprintln!("{problem}");
+        }
+        // The entries are taken out so that the app state is available to the other
+        // handlers while Discord is queried.
+        let entries: Vec<SelfRoleEntry> = app_state.self_role_msgs.entries().collect();
         {
             let mut wlock: RwLockWriteGuard<TypeMap> = ctx.data.write().await;
             wlock.insert::<AppStateKey>(app_state);
             wlock.insert::<PgPoolKey>(self.pool.clone());
         }
+        let reconciliation =
+            app_state::sync::reconcile_self_roles(&ctx.http, &self.cfg, entries, &member_roles)
+                .await;
+
+        match reconciliation {
+            Ok(stale_entries) => problems.extend(stale_entries),
+            Err(e) => {
+                eprintln!("Failed to reconcile self-assigned roles: {e}");
+            }
+        };
+        if !problems.is_empty() {
+            let mut msg_builder = MessageBuilder::new();
+            msg_builder.push("While syncing self-assigned roles, I found some problems:\n");
+            for problem in problems.iter().take(LISTED_PROBLEMS_LIMIT) {
+                msg_builder.push(problem).push("\n");
+            }
+            let unlisted: usize = problems.len().saturating_sub(LISTED_PROBLEMS_LIMIT);
+            if unlisted > 0 {
+                msg_builder.push(format!("and {unlisted} more\n"));
+            }
+            let report = msg_builder.build();
+            if let Err(e) = self
+                .discord_bot_channel()
+                .send_message(&ctx.http, |m| {
+                    m.content(&report).allowed_mentions(|am| am.empty_parse())
+                })
+                .await
+            {
+                eprintln!("Failed to report the self-role problems: {e}");
+            }
+        }
+
+        self.spawn_voice_ticker(&ctx);
+        self.spawn_decay_ticker(&ctx);
+        self.spawn_season_ticker(&ctx);
+        self.spawn_exp_event_ticker(&ctx);
 
         let bot_name: &str = &ready.user.name;
         println!("{bot_name} is at your service! 🌸");
@@ -98,9 +165,27 @@ impl EventHandler for MainBot {
             sorted_earned_roles,
             // Underscore pattern is used to silence the unused variable warning.
             self_role_msgs: _,
+            exp_settings,
+            exp_multipliers,
+            msg_activity,
+            voice_activity: _,
+            rank_card_theme: _,
+            earned_role_mode,
+            level_up_settings: _,
+            level_up_ping_opt_outs: _,
+            season_settings: _,
+            streak_settings: _,
+            exp_events,
         } = app_state;
         if reqd_prompts
-            .handle_if_pending(self, &ctx, &msg, sorted_earned_roles, users)
+            .handle_if_pending(
+                self,
+                &ctx,
+                &msg,
+                sorted_earned_roles,
+                users,
+                *earned_role_mode,
+            )
             .await
             .is_break()
         {
@@ -115,29 +200,123 @@ impl EventHandler for MainBot {
             return;
         }
         println!("{}: {}", msg.author.name, msg.content);
+        // Messages in the excluded channels don't even start the cooldown
+        if exp_multipliers.channel(msg.channel_id) == 0.0 {
+            return;
+        }
+        if !msg_activity.record_msg(exp_settings, msg.author.id, &msg.content) {
+            return;
+        }
 
-        let res: crate::util::Result<Exp> = {
-            let author: Member = msg.member(&ctx).await.unwrap_or_else(|e| {
-                panic!("Failed to get member info for the message author: {e}")
+        let author: Member = msg
+            .member(&ctx)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to get member info for the message author: {e}"));
+        let delta: i64 = exp_multipliers.apply(EXP_PER_MSG, msg.channel_id, &author.roles);
+        if delta == 0 {
+            return;
+        }
+        let mut changes: Vec<ExpChange> = vec![ExpChange {
+            delta,
+            source: ExpSource::Message,
+            channel_id: Some(msg.channel_id),
+        }];
+        // The bonus of the active exp events is recorded separately in the exp ledger
+        let bonus: i64 = exp_events.apply(delta, msg.channel_id, event::unix_now()) - delta;
+        if bonus != 0 {
+            changes.push(ExpChange {
+                delta: bonus,
+                source: ExpSource::Event,
+                channel_id: Some(msg.channel_id),
             });
+        }
+        let res: crate::util::Result<(Exp, Option<LevelUpAnnouncement>)> =
             app_state::sync::add_signed_exp(
-                &ctx.http,
-                &self.cfg,
-                app_state,
-                &self.pool,
-                &author,
-                EXP_PER_MSG,
+                &ctx.http, &self.cfg, app_state, &self.pool, &author, &changes, None,
             )
-            .await
-        };
+            .await;
 
+        let mut announcements: Vec<LevelUpAnnouncement> = Vec::new();
         match res {
-            Ok(exp) => {
+            Ok((exp, announcement)) => {
                 println!("{}'s exp: {exp:?}", msg.author.name);
+                announcements.extend(announcement);
             }
             Err(e) => {
                 eprintln!("Sqlx error during adjusting experience: {e}");
+                return;
             }
         };
+
+        let res: crate::util::Result<(Option<StreakDay>, Option<LevelUpAnnouncement>)> =
+            app_state::sync::record_streak_day(
+                &ctx.http, &self.cfg, app_state, &self.pool, &author,
+            )
+            .await;
+        match res {
+            Ok((Some(StreakDay { days, bonus }), announcement)) => {
+                println!("{}'s streak: {days} day(s)", msg.author.name);
+                if let Some(bonus) = bonus {
+                    println!(
+                        "{} got {} bonus exp for the streak",
+                        msg.author.name, bonus.0
+                    );
+                }
+                announcements.extend(announcement);
+            }
+            Ok((None, _)) => {}
+            Err(e) => {
+                eprintln!("Error during recording the activity streak: {e}");
+            }
+        };
+        drop(wlock);
+        for announcement in announcements {
+            announcement.post(&ctx.http).await;
+        }
+    }
+
+    async fn voice_state_update(&self, ctx: Context, _old: Option<VoiceState>, _new: VoiceState) {
+        // The cache already reflects the update, so the sessions are refreshed from it
+        voice::award_voice_exp(&ctx, &self.cfg, &self.pool).await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, add_reaction: Reaction) {
+        let Some((user_id, role_id)) = self.self_role_for_reaction(&ctx, &add_reaction).await
+        else {
+            return;
+        };
+        if let Err(e) = ctx
+            .http
+            .add_member_role(self.discord_server_id().0, user_id.0, role_id.0, None)
+            .await
+        {
+            eprintln!("Failed to give the self-assigned role {role_id} to {user_id}: {e}");
+            return;
+        }
+
+        let held_roles: Option<&[RoleId]> = add_reaction.member.as_ref().map(|m| &m.roles[..]);
+        self.revoke_excl_siblings(&ctx, user_id, role_id, held_roles)
+            .await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::MessageComponent(component) = interaction {
+            self.handle_self_role_component(&ctx, &component).await;
+            leaderboard::handle_leaderboard_component(&ctx, &component).await;
+        }
+    }
+
+    async fn reaction_remove(&self, ctx: Context, removed_reaction: Reaction) {
+        let Some((user_id, role_id)) = self.self_role_for_reaction(&ctx, &removed_reaction).await
+        else {
+            return;
+        };
+        if let Err(e) = ctx
+            .http
+            .remove_member_role(self.discord_server_id().0, user_id.0, role_id.0, None)
+            .await
+        {
+            eprintln!("Failed to remove the self-assigned role {role_id} from {user_id}: {e}");
+        }
     }
 }
@@ -1,9 +1,42 @@
 mod bot;
+mod decay;
+mod event;
 mod main_bot;
+mod season;
+mod self_roles;
 #[cfg(test)]
 mod test_bot;
+mod voice;
+
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 pub(crate) use bot::Bot;
 pub(crate) use main_bot::MainBot;
 #[cfg(test)]
 pub(crate) use test_bot::TestBot;
+
+/// Spawns the task that calls `tick` every `period`, unless the `started` flag says
+/// that it has been spawned already.
+///
+/// The flag keeps the tickers from being spawned again when
+/// [`EventHandler::ready`](serenity::client::EventHandler::ready) fires after a reconnection.
+fn spawn_ticker<F, Fut>(started: &'static AtomicBool, period: Duration, mut tick: F)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    if started.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            tick().await;
+        }
+    });
+}
@@ -0,0 +1,79 @@
+use std::{collections::HashMap, sync::atomic::AtomicBool};
+
+use serenity::{
+    model::prelude::{RoleId, UserId},
+    prelude::Context,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{ladder::EarnedRoleChange, sync, type_map_keys::AppStateKey, AppState},
+    db,
+    immut_data::{consts::SEASON_TICK, dynamic::BotCfg},
+};
+
+use super::{spawn_ticker, MainBot};
+
+static SEASON_TICKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+impl MainBot {
+    /// Spawns the task that ends the seasons when they have lasted long enough.
+    pub(super) fn spawn_season_ticker(&self, ctx: &Context) {
+        let ctx = ctx.clone();
+        let cfg = self.cfg.clone();
+        let pool = self.pool.clone();
+        spawn_ticker(&SEASON_TICKER_STARTED, SEASON_TICK, move || {
+            let (ctx, cfg, pool) = (ctx.clone(), cfg.clone(), pool.clone());
+            async move { end_season_if_due(&ctx, &cfg, &pool).await }
+        });
+    }
+}
+
+async fn end_season_if_due(ctx: &Context, cfg: &BotCfg, pool: &PgPool) {
+    match db::season_due(pool).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            eprintln!("Failed to check whether the season should end: {e}");
+            return;
+        }
+    };
+    let member_roles: HashMap<UserId, Vec<RoleId>> =
+        match sync::fetch_member_roles(&ctx.http, cfg).await {
+            Ok(member_roles) => member_roles,
+            Err(e) => {
+                eprintln!("Failed to get the roles of the members for ending the season: {e}");
+                return;
+            }
+        };
+
+    let changes: Vec<EarnedRoleChange> = {
+        let mut wlock = ctx.data.write().await;
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        // An admin might have ended the season since the first check
+        match db::season_due(pool).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                eprintln!("Failed to check whether the season should end: {e}");
+                return;
+            }
+        };
+        match sync::end_season(app_state, pool, &member_roles).await {
+            Ok((number, changes)) => {
+                println!(
+                    "Season {number} has ended. The earned roles of {} member(s) are updated",
+                    changes.len()
+                );
+                changes
+            }
+            Err(e) => {
+                eprintln!("Error during ending the season: {e}");
+                return;
+            }
+        }
+    };
+    sync::apply_earned_role_changes(&ctx.http, cfg, &changes).await;
+}
@@ -0,0 +1,254 @@
+use serenity::{
+    model::{
+        application::{
+            component::ComponentType,
+            interaction::{
+                message_component::MessageComponentInteraction, InteractionResponseType,
+            },
+        },
+        prelude::{Reaction, RoleId, UserId},
+    },
+    prelude::Context,
+    utils::MessageBuilder,
+};
+
+use crate::{
+    app_state::{
+        roles::{Presentation, SelfRoleEntry},
+        type_map_keys::AppStateKey,
+        AppState,
+    },
+    immut_data::consts::{SELF_ROLE_CHOICE_SUFFIX, SELF_ROLE_CUSTOM_ID_PREFIX},
+};
+
+use super::{Bot, MainBot};
+
+impl MainBot {
+    /// Looks up the self-assigned role that corresponds to the reaction.
+    ///
+    /// Returns `None` if the reaction is not on a tracked self-role message,
+    /// if the emoji is not mapped to any role, or if the reaction was made by the bot itself.
+    pub(super) async fn self_role_for_reaction(
+        &self,
+        ctx: &Context,
+        reaction: &Reaction,
+    ) -> Option<(UserId, RoleId)> {
+        if reaction.channel_id != self.discord_self_role_channel() {
+            return None;
+        }
+        let user_id: UserId = reaction.user_id?;
+        if user_id == ctx.cache.current_user_id() {
+            return None;
+        }
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let role_id: RoleId = app_state
+            .self_role_msgs
+            .role_for_reaction(reaction.message_id, &reaction.emoji)?;
+        Some((user_id, role_id))
+    }
+
+    /// Takes away the roles that are mutually exclusive with the granted one
+    /// and removes the reactions that stand for them.
+    ///
+    /// If `held_roles` is `None`, the removal of every sibling role is attempted.
+    pub(super) async fn revoke_excl_siblings(
+        &self,
+        ctx: &Context,
+        user_id: UserId,
+        role_id: RoleId,
+        held_roles: Option<&[RoleId]>,
+    ) {
+        let excl_siblings: Vec<SelfRoleEntry> = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            app_state.self_role_msgs.excl_siblings(role_id)
+        };
+        // Removal of the stale reaction triggers `reaction_remove`, which would take
+        // the sibling role away as well. The role is still removed explicitly because
+        // the member might have obtained it without reacting.
+        for SelfRoleEntry {
+            message_id,
+            role_id: sibling_id,
+            reaction,
+            presentation,
+            ..
+        } in excl_siblings
+        {
+            let has_sibling = match held_roles {
+                Some(held_roles) => held_roles.contains(&sibling_id),
+                None => true,
+            };
+            if has_sibling {
+                if let Err(e) = ctx
+                    .http
+                    .remove_member_role(self.discord_server_id().0, user_id.0, sibling_id.0, None)
+                    .await
+                {
+                    eprintln!(
+                        "Failed to remove the exclusive role {sibling_id} from {user_id}: {e}"
+                    );
+                }
+            }
+            if presentation != Presentation::Reactions {
+                continue;
+            }
+            if let Err(e) = ctx
+                .http
+                .delete_reaction(
+                    self.discord_self_role_channel().0,
+                    message_id.0,
+                    Some(user_id.0),
+                    &reaction,
+                )
+                .await
+            {
+                eprintln!("Failed to remove the stale reaction of {user_id} on {message_id}: {e}");
+            }
+        }
+    }
+
+    /// Grants or revokes the self-assigned roles chosen via the buttons or the dropdown
+    /// attached to a self-role message and replies to the member ephemerally.
+    ///
+    /// The interaction is acknowledged before the roles are changed.
+    ///
+    /// Interactions with other components are ignored.
+    pub(super) async fn handle_self_role_component(
+        &self,
+        ctx: &Context,
+        component: &MessageComponentInteraction,
+    ) {
+        let Some(suffix) = component
+            .data
+            .custom_id
+            .strip_prefix(SELF_ROLE_CUSTOM_ID_PREFIX)
+        else {
+            return;
+        };
+        let user_id: UserId = component.user.id;
+        let held_roles: Vec<RoleId> = component
+            .member
+            .as_ref()
+            .map(|m| m.roles.clone())
+            .unwrap_or_default();
+        let offered_roles: Vec<RoleId> = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            app_state
+                .self_role_msgs
+                .msg_entries(component.message.id)
+                .into_iter()
+                .map(|entry| entry.role_id)
+                .collect()
+        };
+
+        let (granted, revoked): (Vec<RoleId>, Vec<RoleId>) = match component.data.component_type {
+            ComponentType::Button => {
+                let Some(role_id) = suffix.parse::<u64>().ok().map(RoleId) else {
+                    return;
+                };
+                if held_roles.contains(&role_id) {
+                    (Vec::new(), vec![role_id])
+                } else {
+                    (vec![role_id], Vec::new())
+                }
+            }
+            ComponentType::SelectMenu if suffix == SELF_ROLE_CHOICE_SUFFIX => {
+                let chosen: Vec<RoleId> = component
+                    .data
+                    .values
+                    .iter()
+                    .filter_map(|v| v.parse::<u64>().ok().map(RoleId))
+                    .collect();
+                let revoked = offered_roles
+                    .iter()
+                    .filter(|r| !chosen.contains(r) && held_roles.contains(r))
+                    .copied()
+                    .collect();
+                (chosen, revoked)
+            }
+            _ => return,
+        };
+
+        // Discord expects the interaction to be acknowledged within 3 seconds,
+        // which the role changes below can take longer than.
+        if let Err(e) = component
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    .interaction_response_data(|d| d.ephemeral(true))
+            })
+            .await
+        {
+            eprintln!("Failed to acknowledge the self-role interaction: {e}");
+            return;
+        }
+
+        let mut msg_builder = MessageBuilder::new();
+        for role_id in granted {
+            // The components can be outdated, so the role is checked to be still offered.
+            if !offered_roles.contains(&role_id) {
+                msg_builder.push("This role is no longer self-assignable.\n");
+                continue;
+            }
+            if let Err(e) = ctx
+                .http
+                .add_member_role(self.discord_server_id().0, user_id.0, role_id.0, None)
+                .await
+            {
+                eprintln!("Failed to give the self-assigned role {role_id} to {user_id}: {e}");
+                msg_builder
+                    .push("I couldn't give you ")
+                    .role(role_id)
+                    .push(" 😢\n");
+                continue;
+            }
+            self.revoke_excl_siblings(ctx, user_id, role_id, Some(&held_roles))
+                .await;
+            msg_builder
+                .push("You now have ")
+                .role(role_id)
+                .push(" 💕\n");
+        }
+        for role_id in revoked {
+            if !offered_roles.contains(&role_id) {
+                continue;
+            }
+            if let Err(e) = ctx
+                .http
+                .remove_member_role(self.discord_server_id().0, user_id.0, role_id.0, None)
+                .await
+            {
+                eprintln!("Failed to remove the self-assigned role {role_id} from {user_id}: {e}");
+                msg_builder
+                    .push("I couldn't take ")
+                    .role(role_id)
+                    .push(" away 😢\n");
+                continue;
+            }
+            msg_builder
+                .push("You no longer have ")
+                .role(role_id)
+                .push("\n");
+        }
+        let mut response = msg_builder.build();
+        if response.is_empty() {
+            response = "Nothing has changed.".to_string();
+        }
+
+        if let Err(e) = component
+            .edit_original_interaction_response(&ctx.http, |r| {
+                r.content(&response).allowed_mentions(|am| am.empty_parse())
+            })
+            .await
+        {
+            eprintln!("Failed to respond to the self-role interaction: {e}");
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::atomic::AtomicBool;
+
+use serenity::{
+    model::prelude::{Member, VoiceState},
+    prelude::Context,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        exp::{ExpChange, ExpSource},
+        level_up::LevelUpAnnouncement,
+        sync,
+        type_map_keys::AppStateKey,
+        voice::{VoiceMember, VoiceReward},
+        AppState,
+    },
+    immut_data::{consts::VOICE_EXP_TICK, dynamic::BotCfg},
+};
+
+use super::{spawn_ticker, MainBot};
+
+static VOICE_TICKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+impl MainBot {
+    /// Spawns the task that regularly rewards the members talking in voice channels.
+    pub(super) fn spawn_voice_ticker(&self, ctx: &Context) {
+        let ctx = ctx.clone();
+        let cfg = self.cfg.clone();
+        let pool = self.pool.clone();
+        spawn_ticker(&VOICE_TICKER_STARTED, VOICE_EXP_TICK, move || {
+            let (ctx, cfg, pool) = (ctx.clone(), cfg.clone(), pool.clone());
+            async move { award_voice_exp(&ctx, &cfg, &pool).await }
+        });
+    }
+}
+
+fn voice_member(ctx: &Context, voice_state: &VoiceState) -> Option<VoiceMember> {
+    let channel_id = voice_state.channel_id?;
+    let is_bot: bool = match &voice_state.member {
+        Some(member) => member.user.bot,
+        None => ctx
+            .cache
+            .user(voice_state.user_id)
+            .is_some_and(|user| user.bot),
+    };
+    let is_muted = voice_state.mute
+        || voice_state.self_mute
+        || voice_state.deaf
+        || voice_state.self_deaf
+        || voice_state.suppress;
+    Some(VoiceMember {
+        user_id: voice_state.user_id,
+        channel_id,
+        is_bot,
+        is_muted,
+    })
+}
+
+/// Brings the voice sessions up to date with the cached voice states of the server
+/// and rewards the members for the whole minutes they have spent talking.
+///
+/// It is called on every voice state update and regularly by the voice ticker.
+pub(super) async fn award_voice_exp(ctx: &Context, cfg: &BotCfg, pool: &PgPool) {
+    let Some(voice_members) = ctx.cache.guild_field(cfg.discord_server_id, |guild| {
+        guild
+            .voice_states
+            .values()
+            .filter_map(|voice_state| voice_member(ctx, voice_state))
+            .collect::<Vec<VoiceMember>>()
+    }) else {
+        return;
+    };
+
+    let (rewards, exp_per_min): (Vec<VoiceReward>, i64) = {
+        let mut wlock = ctx.data.write().await;
+        // The voice states can change before the app state is loaded in `ready`
+        let Some(app_state) = wlock.get_mut::<AppStateKey>() else {
+            return;
+        };
+        let app_state: &mut AppState = app_state;
+        let rewards: Vec<VoiceReward> = app_state.voice_activity.refresh(&voice_members);
+        (rewards, app_state.exp_settings.voice_exp_per_min)
+    };
+    if exp_per_min == 0 {
+        return;
+    }
+
+    // The lock is taken for each member separately so that the other handlers
+    // don't wait for all the rewards to be given out.
+    for VoiceReward {
+        user_id,
+        channel_id,
+        minutes,
+    } in rewards
+    {
+        let member: Member = match ctx.cache.member(cfg.discord_server_id, user_id) {
+            Some(member) => member,
+            None => match cfg.discord_server_id.member(&ctx.http, user_id).await {
+                Ok(member) => member,
+                Err(e) => {
+                    eprintln!("Failed to get member info for the voice activity of {user_id}: {e}");
+                    continue;
+                }
+            },
+        };
+        let base: i64 = exp_per_min.saturating_mul(i64::try_from(minutes).unwrap_or(i64::MAX));
+        let announcement: Option<LevelUpAnnouncement> = {
+            let mut wlock = ctx.data.write().await;
+            let app_state: &mut AppState = wlock
+                .get_mut::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            let delta: i64 = app_state
+                .exp_multipliers
+                .apply(base, channel_id, &member.roles);
+            if delta == 0 {
+                continue;
+            }
+            let change = ExpChange {
+                delta,
+                source: ExpSource::Voice,
+                channel_id: Some(channel_id),
+            };
+            match sync::add_signed_exp(&ctx.http, cfg, app_state, pool, &member, &[change], None)
+                .await
+            {
+                Ok((exp, announcement)) => {
+                    println!(
+                        "{}'s exp after {minutes} min in voice: {exp:?}",
+                        member.user.name
+                    );
+                    announcement
+                }
+                Err(e) => {
+                    eprintln!("Error during adjusting experience for voice activity: {e}");
+                    None
+                }
+            }
+        };
+        if let Some(announcement) = announcement {
+            announcement.post(&ctx.http).await;
+        }
+    }
+}
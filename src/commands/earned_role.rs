@@ -0,0 +1,368 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::{Message, RoleId},
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::app_state::{
+    exp::Exp,
+    ladder::{EarnedRoleChange, EarnedRoleMode},
+    rank::RankRole,
+    sync,
+    type_map_keys::{AppStateKey, PgPoolKey},
+    AppState, EarnedRole,
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+#[command("earned")]
+#[only_in(guilds)]
+#[description = "Command set for managing the earned roles, the roles that members get \
+for collecting experience points."]
+#[sub_commands(list, set_exp, remove, rename, mode, reconcile)]
+async fn earned_roles(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(
+        ctx,
+        &bot_cfg,
+        msg,
+        &args,
+        EARNED_ROLES_COMMAND.options.sub_commands,
+    )
+    .await
+}
+
+/// Returns the earned roles ordered by the exp needed for them.
+async fn earned_roles_snapshot(ctx: &Context) -> Vec<RankRole> {
+    let rlock = ctx.data.read().await;
+    let app_state: &AppState = rlock
+        .get::<AppStateKey>()
+        .expect("Failed to get the app state from the typemap");
+    app_state.earned_roles()
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Lists the earned roles along with the experience points needed for them."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let earned_roles: Vec<RankRole> = earned_roles_snapshot(ctx).await;
+
+    if earned_roles.is_empty() {
+        return respond(ctx, &bot_cfg, msg, "There are no earned roles.").await;
+    }
+    let mut msg_builder = MessageBuilder::new();
+    msg_builder.push("Earned roles:\n");
+    for RankRole {
+        role_id,
+        exp_needed,
+    } in earned_roles
+    {
+        msg_builder
+            .push("\t")
+            .role(role_id)
+            .push(format!(": {} exp\n", exp_needed.0));
+    }
+    respond(ctx, &bot_cfg, msg, msg_builder.build()).await
+}
+
+#[command("set-exp")]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Changes the experience points needed for the earned role. \
+The members get promoted or demoted accordingly."]
+#[usage = "<role> <exp>"]
+async fn set_exp(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(role_id), Ok(exp_needed)) = (args.single::<RoleId>(), args.single::<u64>()) else {
+        let response = "Usage: `role earned set-exp <role> <exp>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let exp_needed = Exp(exp_needed);
+
+    let earned_roles: Vec<RankRole> = earned_roles_snapshot(ctx).await;
+    if !earned_roles.iter().any(|r| r.role_id == role_id) {
+        let response = MessageBuilder::new()
+            .role(role_id)
+            .push(" is not an earned role.")
+            .build();
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    if let Some(other) = earned_roles
+        .iter()
+        .find(|r| r.role_id != role_id && r.exp_needed == exp_needed)
+    {
+        let response = MessageBuilder::new()
+            .role(other.role_id)
+            .push(format!(" already needs {} exp.", exp_needed.0))
+            .build();
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let member_roles = sync::fetch_member_roles(&ctx.http, &bot_cfg).await?;
+    let changes: Vec<EarnedRoleChange> = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let AppState {
+            users,
+            sorted_earned_roles,
+            earned_role_mode,
+            ..
+        } = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_earned_role_exp(
+            sorted_earned_roles,
+            users,
+            *earned_role_mode,
+            &pool,
+            &member_roles,
+            EarnedRole::new(role_id, exp_needed),
+        )
+        .await?
+    };
+    sync::apply_earned_role_changes(&ctx.http, &bot_cfg, &changes).await;
+
+    let response = MessageBuilder::new()
+        .role(role_id)
+        .push(format!(" now needs {} exp.", exp_needed.0))
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Stops treating the role as an earned role. The Discord role itself is kept, \
+but the members that have it get the earned role below it instead."]
+#[usage = "<role>"]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(role_id) = args.single::<RoleId>() else {
+        let response = "Usage: `role earned remove <role>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+
+    let earned_roles: Vec<RankRole> = earned_roles_snapshot(ctx).await;
+    if !earned_roles.iter().any(|r| r.role_id == role_id) {
+        let response = MessageBuilder::new()
+            .role(role_id)
+            .push(" is not an earned role.")
+            .build();
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let member_roles = sync::fetch_member_roles(&ctx.http, &bot_cfg).await?;
+    let changes: Vec<EarnedRoleChange> = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let AppState {
+            users,
+            sorted_earned_roles,
+            earned_role_mode,
+            ..
+        } = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::remove_earned_role(
+            sorted_earned_roles,
+            users,
+            *earned_role_mode,
+            &pool,
+            &member_roles,
+            role_id,
+        )
+        .await?
+    };
+    sync::apply_earned_role_changes(&ctx.http, &bot_cfg, &changes).await;
+
+    let response = MessageBuilder::new()
+        .role(role_id)
+        .push(" is no longer an earned role.")
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Renames the earned role."]
+#[usage = "<role> <name>"]
+async fn rename(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(role_id) = args.single::<RoleId>() else {
+        let response = "Usage: `role earned rename <role> <name>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let name: &str = args.rest().trim();
+    if name.is_empty() {
+        let response = "Usage: `role earned rename <role> <name>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let earned_roles: Vec<RankRole> = earned_roles_snapshot(ctx).await;
+    if !earned_roles.iter().any(|r| r.role_id == role_id) {
+        let response = MessageBuilder::new()
+            .role(role_id)
+            .push(" is not an earned role.")
+            .build();
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    bot_cfg
+        .discord_server_id
+        .edit_role(&ctx.http, role_id, |r| r.name(name))
+        .await?;
+
+    let response = MessageBuilder::new()
+        .push("The earned role has been renamed to ")
+        .role(role_id)
+        .push(".")
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Shows or changes what happens to the earned roles of a member on promotion. \
+In the `replacing` mode, the new earned role replaces the previous one. \
+In the `cumulative` mode, members keep every earned role that they have reached. \
+Changing the mode updates the roles of all members."]
+#[usage = "[replacing | cumulative]"]
+async fn mode(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+
+    if args.is_empty() {
+        let mode: EarnedRoleMode = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            app_state.earned_role_mode
+        };
+        let response = format!("The earned roles are in the `{}` mode.", mode.as_str());
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    let Ok(Ok(mode)) = args
+        .single::<String>()
+        .map(|mode| mode.parse::<EarnedRoleMode>())
+    else {
+        let response = "Usage: `role earned mode [replacing | cumulative]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+
+    let member_roles = sync::fetch_member_roles(&ctx.http, &bot_cfg).await?;
+    let changes: Vec<EarnedRoleChange> = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        if app_state.earned_role_mode == mode {
+            let response = format!(
+                "The earned roles are already in the `{}` mode.",
+                mode.as_str()
+            );
+            drop(wlock);
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+        sync::set_earned_role_mode(app_state, &pool, &member_roles, mode).await?
+    };
+    sync::apply_earned_role_changes(&ctx.http, &bot_cfg, &changes).await;
+
+    let response = format!(
+        "The earned roles are now in the `{}` mode. The roles of {} member(s) were updated.",
+        mode.as_str(),
+        changes.len()
+    );
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[owners_only]
+#[description = "Gives every member the earned role that their exp is enough for \
+and takes away the other earned roles. With `dry-run`, only reports what would change."]
+#[usage = "[dry-run]"]
+async fn reconcile(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    /// The number of changes listed in the report. The rest are only counted
+    /// so that the report fits into a message.
+    const LISTED_CHANGES_LIMIT: usize = 20;
+
+    let bot_cfg = bot_cfg(ctx).await;
+    let dry_run: bool = match args.single::<String>() {
+        Err(_) => false,
+        Ok(arg) if arg == "dry-run" => true,
+        Ok(_) => {
+            let response = "Usage: `role earned reconcile [dry-run]`";
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+    };
+
+    let member_roles = sync::fetch_member_roles(&ctx.http, &bot_cfg).await?;
+    let changes: Vec<EarnedRoleChange> = {
+        let mut wlock = ctx.data.write().await;
+        let AppState {
+            users,
+            sorted_earned_roles,
+            earned_role_mode,
+            ..
+        } = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::reconcile_earned_roles(
+            sorted_earned_roles,
+            users,
+            &member_roles,
+            &[],
+            *earned_role_mode,
+            dry_run,
+        )
+    };
+    if !dry_run {
+        sync::apply_earned_role_changes(&ctx.http, &bot_cfg, &changes).await;
+    }
+
+    if changes.is_empty() {
+        let response = "The earned roles of all members are up to date.";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    let mut msg_builder = MessageBuilder::new();
+    if dry_run {
+        msg_builder.push(format!("{} member(s) would be changed:\n", changes.len()));
+    } else {
+        msg_builder.push(format!("{} member(s) were changed:\n", changes.len()));
+    }
+    for EarnedRoleChange {
+        user_id,
+        add,
+        remove,
+    } in changes.iter().take(LISTED_CHANGES_LIMIT)
+    {
+        msg_builder.push("\t").mention(user_id);
+        for role_id in add {
+            msg_builder.push(" +").role(role_id);
+        }
+        for role_id in remove {
+            msg_builder.push(" -").role(role_id);
+        }
+        msg_builder.push("\n");
+    }
+    let unlisted: usize = changes.len().saturating_sub(LISTED_CHANGES_LIMIT);
+    if unlisted > 0 {
+        msg_builder.push(format!("\tand {unlisted} more\n"));
+    }
+    respond(ctx, &bot_cfg, msg, msg_builder.build()).await
+}
@@ -0,0 +1,217 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::{ChannelId, Message},
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        event::{self, EventAnnouncement, ExpEvent},
+        sync,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db,
+    immut_data::consts::MAX_EXP_MULTIPLIER,
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+#[command]
+#[only_in(guilds)]
+#[description = "Command set for the timed events that multiply the experience points \
+for messages."]
+#[sub_commands(list, add, cancel)]
+async fn event(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(
+        ctx,
+        &bot_cfg,
+        msg,
+        &args,
+        EVENT_COMMAND.options.sub_commands,
+    )
+    .await
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Lists the active and the upcoming exp events."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let events: Vec<ExpEvent> = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        app_state.exp_events.events().to_vec()
+    };
+
+    let now: i64 = event::unix_now();
+    let events: Vec<ExpEvent> = events
+        .into_iter()
+        .filter(|event| event.ends_at > now)
+        .collect();
+    if events.is_empty() {
+        return respond(ctx, &bot_cfg, msg, "There are no exp events.").await;
+    }
+    let mut msg_builder = MessageBuilder::new();
+    msg_builder.push("Exp events:\n");
+    for event in events {
+        let ExpEvent {
+            id,
+            multiplier,
+            starts_at,
+            ends_at,
+            ..
+        } = event;
+        let state: &str = if event.is_active(now) {
+            "active"
+        } else {
+            "upcoming"
+        };
+        msg_builder.push(format!(
+            "\t`#{id}` {multiplier}x exp{}: <t:{starts_at}:f> – <t:{ends_at}:f> ({state})\n",
+            event.scope()
+        ));
+    }
+    respond(ctx, &bot_cfg, msg, msg_builder.build()).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Schedules an exp event that multiplies the experience points for messages \
+by more than 1 and at most 10. \
+The start and the end are local times in the timezone of the server (see `streak timezone`), \
+written as `YYYY-MM-DDTHH:MM` or as `\"YYYY-MM-DD HH:MM\"`. \
+Without channels, the event applies everywhere."]
+#[usage = "<multiplier> <start> <end> [channel...]"]
+async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    const USAGE: &str = "Usage: `event add <multiplier> <start> <end> [channel...]`";
+
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(multiplier), Ok(start), Ok(end)) = (
+        args.single::<String>(),
+        args.single_quoted::<String>(),
+        args.single_quoted::<String>(),
+    ) else {
+        return respond(ctx, &bot_cfg, msg, USAGE).await;
+    };
+    let multiplier: f64 = match multiplier.trim_end_matches('x').parse::<f64>() {
+        Ok(multiplier) if multiplier > 1.0 && multiplier <= MAX_EXP_MULTIPLIER => multiplier,
+        _ => {
+            let response = "The multiplier must be a number greater than 1 and at most 10.";
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+    };
+    let mut channels: Vec<ChannelId> = Vec::new();
+    while !args.is_empty() {
+        let Ok(channel_id) = args.single::<ChannelId>() else {
+            return respond(ctx, &bot_cfg, msg, USAGE).await;
+        };
+        if !channels.contains(&channel_id) {
+            channels.push(channel_id);
+        }
+    }
+
+    let (pool, timezone): (PgPool, String) = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let pool: PgPool = rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        (pool, app_state.streak_settings.timezone.clone())
+    };
+    let (Some(starts_at), Some(ends_at)) = (
+        db::parse_local_time(&pool, &start, &timezone).await?,
+        db::parse_local_time(&pool, &end, &timezone).await?,
+    ) else {
+        let response = "The times must look like `2024-05-17T18:00` or `\"2024-05-17 18:00\"`.";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    if ends_at <= starts_at {
+        return respond(ctx, &bot_cfg, msg, "The event must end after it starts.").await;
+    }
+    if ends_at <= event::unix_now() {
+        return respond(ctx, &bot_cfg, msg, "The event would have already ended.").await;
+    }
+
+    let event: ExpEvent = {
+        let mut wlock = ctx.data.write().await;
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::add_exp_event(
+            &mut app_state.exp_events,
+            &pool,
+            msg.author.id,
+            multiplier,
+            starts_at,
+            ends_at,
+            channels,
+        )
+        .await?
+    };
+
+    let response = format!(
+        "Scheduled the exp event `#{}`: {multiplier}x exp{} \
+        from <t:{starts_at}:f> to <t:{ends_at}:f>.",
+        event.id,
+        event.scope()
+    );
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Cancels an active or an upcoming exp event."]
+#[usage = "<id>"]
+async fn cancel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Some(id) = args
+        .single::<String>()
+        .ok()
+        .and_then(|id| id.trim_start_matches('#').parse::<i64>().ok())
+    else {
+        return respond(ctx, &bot_cfg, msg, "Usage: `event cancel <id>`").await;
+    };
+
+    let cancelled: Option<ExpEvent> = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::cancel_exp_event(&mut app_state.exp_events, &pool, id).await?
+    };
+
+    let Some(cancelled) = cancelled else {
+        let response = format!("There is no active or upcoming exp event `#{id}`.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    // The members were told that the event has started, so they are told that it's over
+    if cancelled.start_announced && cancelled.is_active(event::unix_now()) {
+        let content: String = EventAnnouncement::Ended(cancelled).content();
+        if let Err(e) = bot_cfg
+            .discord_bot_channel
+            .send_message(&ctx.http, |m| {
+                m.content(&content).allowed_mentions(|am| am.empty_parse())
+            })
+            .await
+        {
+            eprintln!("Failed to announce the end of the cancelled exp event: {e}");
+        }
+    }
+    let response = format!("The exp event `#{id}` has been cancelled.");
+    respond(ctx, &bot_cfg, msg, response).await
+}
@@ -0,0 +1,493 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::{AttachmentType, ChannelId, Member, Message, RoleId, UserId},
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        exp::{Exp, ExpAdjustment, ExpChange, ExpSource},
+        exp_rules::{ExpMultipliers, ExpSetting},
+        level_up::LevelUpAnnouncement,
+        sync,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db,
+    immut_data::consts::MAX_EXP_MULTIPLIER,
+    rank_card::chart::{self, ExpChart},
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+#[command]
+#[only_in(guilds)]
+#[description = "Command set for managing experience points."]
+#[sub_commands(config, multiplier, give, take, set, history)]
+async fn exp(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(ctx, &bot_cfg, msg, &args, EXP_COMMAND.options.sub_commands).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Shows or changes the rules that decide which activity earns experience points. \
+`cooldown` is the number of seconds between two rewarded messages of a member, \
+`min-chars` is the minimum number of letters and digits in a rewarded message, \
+`ignore-duplicates` (`on` or `off`) stops rewarding a message that repeats the previous one, \
+`voice-exp` is the number of experience points for a minute of talking in a voice channel, \
+`decay-after` is the number of days without activity after which the exp starts to decay \
+(`0` turns the decay off), `decay-percent` is the percentage of the exp that decays \
+every `decay-period` days, and `decay-floor` is the exp that never decays."]
+#[usage = "[<cooldown | min-chars | ignore-duplicates | voice-exp \
+| decay-after | decay-percent | decay-period | decay-floor> <value>]"]
+async fn config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+
+    if args.is_empty() {
+        let response: String = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            let mut msg_builder = MessageBuilder::new();
+            msg_builder.push("Experience settings:\n");
+            for setting in app_state.exp_settings.to_settings() {
+                msg_builder.push(format!("\t{setting}\n"));
+            }
+            msg_builder.build()
+        };
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let (Ok(name), Ok(value)) = (args.single::<String>(), args.single::<String>()) else {
+        let response = "Usage: `exp config [<name> <value>]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let setting: ExpSetting = match ExpSetting::parse(&name, &value) {
+        Ok(setting) => setting,
+        Err(problem) => return respond(ctx, &bot_cfg, msg, problem).await,
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_exp_setting(&mut app_state.exp_settings, &pool, setting).await?;
+    }
+
+    respond(ctx, &bot_cfg, msg, format!("Updated {setting}")).await
+}
+
+/// Parses the multiplier given to a command, `reset` stands for the removal of the multiplier.
+fn parse_multiplier(multiplier: &str) -> Result<Option<f64>, String> {
+    if multiplier == "reset" {
+        return Ok(None);
+    }
+    match multiplier.parse::<f64>() {
+        Ok(multiplier) if (0.0..=MAX_EXP_MULTIPLIER).contains(&multiplier) => Ok(Some(multiplier)),
+        _ => Err(format!(
+            "The multiplier must be a number from 0 to {MAX_EXP_MULTIPLIER} or `reset`."
+        )),
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Command set for managing the multipliers of the experience points \
+for messages. The multiplier of the channel and the multipliers of all roles of the author \
+are multiplied together, up to 10 in total."]
+#[sub_commands(channel, role, list)]
+async fn multiplier(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(
+        ctx,
+        &bot_cfg,
+        msg,
+        &args,
+        MULTIPLIER_COMMAND.options.sub_commands,
+    )
+    .await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Sets the multiplier of the experience points for messages posted in the channel. \
+The multiplier is at most 10, 0 excludes the channel, \
+`reset` restores the default multiplier 1."]
+#[usage = "<channel> <multiplier | reset>"]
+async fn channel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(channel_id), Ok(multiplier)) = (args.single::<ChannelId>(), args.single::<String>())
+    else {
+        let response = "Usage: `exp multiplier channel <channel> <multiplier | reset>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let multiplier: Option<f64> = match parse_multiplier(&multiplier) {
+        Ok(multiplier) => multiplier,
+        Err(problem) => return respond(ctx, &bot_cfg, msg, problem).await,
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_channel_exp_multiplier(
+            &mut app_state.exp_multipliers,
+            &pool,
+            channel_id,
+            multiplier,
+        )
+        .await?;
+    }
+
+    let response = MessageBuilder::new()
+        .push("Messages in ")
+        .channel(channel_id)
+        .push(format!(
+            " are now worth x{} exp.",
+            multiplier.unwrap_or(1.0)
+        ))
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Sets the multiplier of the experience points for messages \
+of the members with the role. The multiplier is at most 10, \
+`reset` restores the default multiplier 1."]
+#[usage = "<role> <multiplier | reset>"]
+async fn role(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(role_id), Ok(multiplier)) = (args.single::<RoleId>(), args.single::<String>()) else {
+        let response = "Usage: `exp multiplier role <role> <multiplier | reset>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let multiplier: Option<f64> = match parse_multiplier(&multiplier) {
+        Ok(multiplier) => multiplier,
+        Err(problem) => return respond(ctx, &bot_cfg, msg, problem).await,
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_role_exp_multiplier(&mut app_state.exp_multipliers, &pool, role_id, multiplier)
+            .await?;
+    }
+
+    let response = MessageBuilder::new()
+        .push("Messages of the members with ")
+        .role(role_id)
+        .push(format!(
+            " are now worth x{} exp.",
+            multiplier.unwrap_or(1.0)
+        ))
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "See the list of exp multipliers."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let response: String = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let ExpMultipliers { channels, roles } = &app_state.exp_multipliers;
+        let mut msg_builder = MessageBuilder::new();
+        msg_builder.push("Channel multipliers:\n");
+        for (channel_id, multiplier) in channels {
+            msg_builder
+                .push("\t")
+                .channel(*channel_id)
+                .push(format!(": x{multiplier}\n"));
+        }
+        msg_builder.push("Role multipliers:\n");
+        for (role_id, multiplier) in roles {
+            msg_builder
+                .push("\t")
+                .role(*role_id)
+                .push(format!(": x{multiplier}\n"));
+        }
+        msg_builder.build()
+    };
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+/// The way a moderator adjusts the exp of a member.
+#[derive(Debug, Clone, Copy)]
+enum Adjustment {
+    Give,
+    Take,
+    Set,
+}
+
+impl Adjustment {
+    fn name(self) -> &'static str {
+        match self {
+            Adjustment::Give => "give",
+            Adjustment::Take => "take",
+            Adjustment::Set => "set",
+        }
+    }
+
+    /// Returns the change of the exp that brings `current` exp to the requested one.
+    fn delta(self, current: Exp, amount: u64) -> i64 {
+        let amount = i64::try_from(amount).unwrap_or(i64::MAX);
+        match self {
+            Adjustment::Give => amount,
+            Adjustment::Take => -amount,
+            Adjustment::Set => amount.saturating_sub(current.to_i64()),
+        }
+    }
+}
+
+/// Adjusts the exp of the member given by the arguments and records the adjustment
+/// in the audit trail.
+async fn adjust_exp(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+    adjustment: Adjustment,
+) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(user_id), Ok(amount)) = (args.single::<UserId>(), args.single::<u64>()) else {
+        let response = format!(
+            "Usage: `exp {} <member> <amount> [reason]`",
+            adjustment.name()
+        );
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let reason: Option<&str> = Some(args.rest().trim()).filter(|reason| !reason.is_empty());
+    let member: Member = bot_cfg.discord_server_id.member(ctx, user_id).await?;
+    if member.user.bot {
+        return respond(ctx, &bot_cfg, msg, "Bots don't collect exp.").await;
+    }
+
+    let (old_exp, new_exp, announcement): (Exp, Exp, Option<LevelUpAnnouncement>) = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let Some(old_exp) = app_state.rank(user_id).map(|rank| rank.exp) else {
+            drop(wlock);
+            let response = MessageBuilder::new()
+                .mention(&user_id)
+                .push(" has no rank on this server.")
+                .build();
+            return respond(ctx, &bot_cfg, msg, response).await;
+        };
+        let delta: i64 = adjustment.delta(old_exp, amount);
+        let change = ExpChange {
+            delta,
+            source: ExpSource::Admin,
+            channel_id: None,
+        };
+        let adjustment = ExpAdjustment {
+            actor_id: msg.author.id,
+            reason,
+        };
+        let (new_exp, announcement) = sync::add_signed_exp(
+            &ctx.http,
+            &bot_cfg,
+            app_state,
+            &pool,
+            &member,
+            &[change],
+            Some(adjustment),
+        )
+        .await?;
+        (old_exp, new_exp, announcement)
+    };
+    if let Some(announcement) = announcement {
+        announcement.post(&ctx.http).await;
+    }
+
+    let response = MessageBuilder::new()
+        .mention(&user_id)
+        .push(format!(" now has {} exp (was {}).", new_exp.0, old_exp.0))
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Gives experience points to the member. The adjustment is recorded \
+along with the optional reason."]
+#[usage = "<member> <amount> [reason]"]
+async fn give(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    adjust_exp(ctx, msg, args, Adjustment::Give).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Takes experience points from the member, but never below 0. \
+The adjustment is recorded along with the optional reason."]
+#[usage = "<member> <amount> [reason]"]
+async fn take(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    adjust_exp(ctx, msg, args, Adjustment::Take).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Sets the experience points of the member. The adjustment is recorded \
+along with the optional reason."]
+#[usage = "<member> <amount> [reason]"]
+async fn set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    adjust_exp(ctx, msg, args, Adjustment::Set).await
+}
+
+/// The periods that the exp history can be summarized by.
+#[derive(Debug, Clone, Copy)]
+enum HistoryPeriod {
+    Day,
+    Week,
+}
+
+impl HistoryPeriod {
+    /// The name of the period understood by `date_trunc` of PostgreSQL.
+    fn as_str(self) -> &'static str {
+        match self {
+            HistoryPeriod::Day => "day",
+            HistoryPeriod::Week => "week",
+        }
+    }
+
+    /// The number of the periods in the history.
+    fn count(self) -> i32 {
+        match self {
+            HistoryPeriod::Day => 14,
+            HistoryPeriod::Week => 12,
+        }
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Summarizes the experience points gained by the member per day (the last 14 days) \
+or per week (the last 12 weeks, the default), and by the source of the exp. \
+With `chart`, the summary is drawn as a chart. Without a member, shows your own history."]
+#[usage = "[member] [day | week] [chart]"]
+async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    const USAGE: &str = "Usage: `exp history [member] [day | week] [chart]`";
+
+    let bot_cfg = bot_cfg(ctx).await;
+    let user_id: UserId = args.single::<UserId>().unwrap_or(msg.author.id);
+    let mut period = HistoryPeriod::Week;
+    let mut as_chart = false;
+    for arg in args.iter::<String>() {
+        match arg.as_deref() {
+            Ok("day" | "days" | "daily") => period = HistoryPeriod::Day,
+            Ok("week" | "weeks" | "weekly") => period = HistoryPeriod::Week,
+            Ok("chart") => as_chart = true,
+            _ => return respond(ctx, &bot_cfg, msg, USAGE).await,
+        }
+    }
+
+    let pool: PgPool = {
+        let rlock = ctx.data.read().await;
+        rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone()
+    };
+    let bars: Vec<(String, i64)> =
+        db::exp_history(&pool, user_id, period.as_str(), period.count()).await?;
+    let by_source: Vec<(String, i64)> =
+        db::exp_history_by_source(&pool, user_id, period.as_str(), period.count()).await?;
+
+    let mut msg_builder = MessageBuilder::new();
+    msg_builder
+        .push("Exp history of ")
+        .mention(&user_id)
+        .push(format!(" per {} (UTC):\n", period.as_str()));
+    if !as_chart {
+        for (label, delta) in &bars {
+            msg_builder.push(format!("\t{label}: {delta:+}\n"));
+        }
+    }
+    let total: i64 = bars.iter().map(|(_, delta)| delta).sum();
+    msg_builder.push(format!("Total: {total:+}\n"));
+    for (source, delta) in &by_source {
+        msg_builder.push(format!("\t{source}: {delta:+}\n"));
+    }
+    let summary: String = msg_builder.build();
+
+    if !as_chart {
+        return respond(ctx, &bot_cfg, msg, summary).await;
+    }
+
+    let theme = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        app_state.rank_card_theme
+    };
+    let display_name: String = match bot_cfg.discord_server_id.member(ctx, user_id).await {
+        Ok(member) => member.display_name().to_string(),
+        Err(_) => user_id.to_string(),
+    };
+    let chart = ExpChart {
+        title: format!("{display_name} - exp per {}", period.as_str()),
+        // The labels are shortened from YYYY-MM-DD to MM-DD to fit under the bars
+        bars: bars
+            .into_iter()
+            .map(|(label, delta)| (label.get(5..).unwrap_or(&label).to_string(), delta))
+            .collect(),
+    };
+    let png: Vec<u8> = chart::render(&chart, &theme)?;
+
+    let content = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(" ")
+        .push(summary)
+        .build();
+    bot_cfg
+        .discord_bot_channel
+        .send_message(&ctx.http, |m| {
+            m.content(&content)
+                .add_file(AttachmentType::Bytes {
+                    data: png.into(),
+                    filename: "exp_history.png".to_string(),
+                })
+                .allowed_mentions(|am| am.empty_parse().users([msg.author.id]))
+        })
+        .await?;
+    if msg.channel_id != bot_cfg.discord_bot_channel {
+        msg.delete(&ctx).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,352 @@
+use serenity::{
+    builder::{CreateComponents, CreateEmbed},
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{
+        application::{
+            component::ButtonStyle,
+            interaction::{
+                message_component::MessageComponentInteraction, InteractionResponseType,
+            },
+        },
+        prelude::{Message, RoleId, UserId},
+    },
+    prelude::Context,
+    utils::{Colour, MessageBuilder},
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        exp::Exp,
+        rank::RankRole,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db::{self, dao},
+    immut_data::{
+        consts::{LEADERBOARD_CUSTOM_ID_PREFIX, LEADERBOARD_PAGE_SIZE},
+        dynamic::BotCfg,
+    },
+};
+
+use super::{bot_cfg, respond};
+
+/// A line of the leaderboard.
+struct Standing {
+    position: i64,
+    user_id: UserId,
+    exp: Exp,
+    earned_role: Option<RoleId>,
+}
+
+impl From<dao::SeasonStanding> for Standing {
+    fn from(standing: dao::SeasonStanding) -> Self {
+        let dao::SeasonStanding {
+            position,
+            discord_id,
+            exp,
+            earned_role_id,
+        } = standing;
+        #[allow(clippy::cast_sign_loss)]
+        let user_id = UserId(discord_id as u64);
+        #[allow(clippy::cast_sign_loss)]
+        let earned_role = earned_role_id.map(|role_id| RoleId(role_id as u64));
+        Standing {
+            position,
+            user_id,
+            exp: Exp::from_i64(exp),
+            earned_role,
+        }
+    }
+}
+
+/// Returns the custom id of the button that shows the page of the leaderboard.
+fn page_custom_id(season: Option<i64>, page: i64) -> String {
+    match season {
+        Some(season) => format!("{LEADERBOARD_CUSTOM_ID_PREFIX}{page}:{season}"),
+        None => format!("{LEADERBOARD_CUSTOM_ID_PREFIX}{page}"),
+    }
+}
+
+/// Returns a page of the current standings of the members on the server.
+///
+/// The earned roles are the ones that the members have reached with their exp.
+async fn current_standings(
+    ctx: &Context,
+    pool: &PgPool,
+    caller: UserId,
+    offset: i64,
+) -> Result<(Vec<Standing>, Option<(i64, Exp)>), sqlx::Error> {
+    let members: Vec<dao::ServerMember> =
+        db::leaderboard_page(pool, LEADERBOARD_PAGE_SIZE, offset).await?;
+    let caller_position: Option<(i64, Exp)> = db::leaderboard_position(pool, caller).await?;
+
+    let rlock = ctx.data.read().await;
+    let app_state: &AppState = rlock
+        .get::<AppStateKey>()
+        .expect("Failed to get the app state from the typemap");
+    let standings: Vec<Standing> = (offset + 1..)
+        .zip(members)
+        .map(|(position, dao::ServerMember { discord_id, exp })| {
+            #[allow(clippy::cast_sign_loss)]
+            let user_id = UserId(discord_id as u64);
+            let exp = Exp::from_i64(exp);
+            let earned_role = app_state
+                .earned_role_for_exp(exp)
+                .map(|RankRole { role_id, .. }| role_id);
+            Standing {
+                position,
+                user_id,
+                exp,
+                earned_role,
+            }
+        })
+        .collect();
+    Ok((standings, caller_position))
+}
+
+/// Returns a page of the final standings of an archived season.
+async fn season_standings(
+    pool: &PgPool,
+    season: i64,
+    caller: UserId,
+    offset: i64,
+) -> Result<(Vec<Standing>, Option<(i64, Exp)>), sqlx::Error> {
+    let standings: Vec<Standing> =
+        db::season_standings_page(pool, season, LEADERBOARD_PAGE_SIZE, offset)
+            .await?
+            .into_iter()
+            .map(Standing::from)
+            .collect();
+    let caller_position: Option<(i64, Exp)> = db::season_standing(pool, season, caller)
+        .await?
+        .map(|standing| (standing.position, Exp::from_i64(standing.exp)));
+    Ok((standings, caller_position))
+}
+
+/// Builds the embed with the page of the leaderboard and the buttons that turn the pages.
+///
+/// `season` is the number of an archived season, or `None` for the current standings.
+/// `page` is 0-based and gets clamped to the existing pages. The position of the caller
+/// is shown in the footer so that it's visible on every page.
+async fn render_page(
+    ctx: &Context,
+    pool: &PgPool,
+    caller: UserId,
+    season: Option<i64>,
+    page: i64,
+) -> Result<(CreateEmbed, CreateComponents), sqlx::Error> {
+    let member_count: i64 = match season {
+        Some(season) => db::season(pool, season)
+            .await?
+            .map_or(0, |season| season.member_count),
+        None => db::count_server_members(pool).await?,
+    };
+    let page_count: i64 =
+        ((member_count + LEADERBOARD_PAGE_SIZE - 1) / LEADERBOARD_PAGE_SIZE).max(1);
+    let page: i64 = page.clamp(0, page_count - 1);
+    let offset: i64 = page * LEADERBOARD_PAGE_SIZE;
+    let (standings, caller_position) = match season {
+        Some(season) => season_standings(pool, season, caller, offset).await?,
+        None => current_standings(ctx, pool, caller, offset).await?,
+    };
+
+    let mut msg_builder = MessageBuilder::new();
+    for Standing {
+        position,
+        user_id,
+        exp,
+        earned_role,
+    } in standings
+    {
+        msg_builder
+            .push(format!("`#{position}` "))
+            .mention(&user_id)
+            .push(format!(" — {} exp", exp.0));
+        if let Some(role_id) = earned_role {
+            msg_builder.push(" — ").role(role_id);
+        }
+        msg_builder.push("\n");
+    }
+    let description: String = msg_builder.build();
+    let footer: String = match caller_position {
+        Some((position, exp)) => format!(
+            "Page {} of {page_count} • You are #{position} of {member_count} with {} exp",
+            page + 1,
+            exp.0
+        ),
+        None => format!("Page {} of {page_count} • You are not ranked", page + 1),
+    };
+    let (title, nobody): (String, &str) = match season {
+        Some(season) => (
+            format!("Leaderboard of season {season}"),
+            "Nobody was ranked in this season.",
+        ),
+        None => ("Leaderboard".to_string(), "Nobody has earned any exp yet."),
+    };
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(title)
+        .colour(Colour::from_rgb(0xD2, 0x04, 0x2D))
+        .description(if description.is_empty() {
+            nobody.to_string()
+        } else {
+            description
+        })
+        .footer(|f| f.text(footer));
+
+    let mut components = CreateComponents::default();
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(page_custom_id(season, page.saturating_sub(1)))
+                .style(ButtonStyle::Secondary)
+                .label("◀")
+                .disabled(page == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(page_custom_id(season, page.saturating_add(1)))
+                .style(ButtonStyle::Secondary)
+                .label("▶")
+                .disabled(page.saturating_add(1) >= page_count)
+        })
+    });
+
+    Ok((embed, components))
+}
+
+/// Turns the page of the leaderboard when one of its buttons is pressed.
+///
+/// The footer shows the position of the member who pressed the button.
+/// Interactions with other components are ignored.
+pub(crate) async fn handle_leaderboard_component(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+) {
+    let Some(suffix) = component
+        .data
+        .custom_id
+        .strip_prefix(LEADERBOARD_CUSTOM_ID_PREFIX)
+    else {
+        return;
+    };
+    let mut parts = suffix.split(':');
+    let Some(Ok(page)) = parts.next().map(str::parse::<i64>) else {
+        return;
+    };
+    // The leaderboards of the archived seasons append the number of the season
+    let season: Option<i64> = match parts.next().map(str::parse::<i64>) {
+        None => None,
+        Some(Ok(season)) => Some(season),
+        Some(Err(_)) => return,
+    };
+    let pool: PgPool = {
+        let rlock = ctx.data.read().await;
+        rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone()
+    };
+
+    let (embed, components) = match render_page(ctx, &pool, component.user.id, season, page).await {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            eprintln!("Failed to render the leaderboard page: {e}");
+            return;
+        }
+    };
+    if let Err(e) = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.set_embed(embed).set_components(components))
+        })
+        .await
+    {
+        eprintln!("Failed to turn the leaderboard page: {e}");
+    }
+}
+
+/// Sends the page of the leaderboard to the bot channel, pinging only the caller.
+async fn send_page(
+    ctx: &Context,
+    bot_cfg: &BotCfg,
+    pool: &PgPool,
+    msg: &Message,
+    season: Option<i64>,
+    page: i64,
+) -> CommandResult {
+    let (embed, components) =
+        render_page(ctx, pool, msg.author.id, season, page.saturating_sub(1)).await?;
+    let mention = MessageBuilder::new().mention(&msg.author).build();
+    bot_cfg
+        .discord_bot_channel
+        .send_message(&ctx.http, |m| {
+            m.content(&mention)
+                .set_embed(embed)
+                .set_components(components)
+                .allowed_mentions(|am| am.empty_parse().users([msg.author.id]))
+        })
+        .await?;
+    if msg.channel_id != bot_cfg.discord_bot_channel {
+        msg.delete(&ctx).await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Shows the members of the server ranked by their experience points."]
+#[usage = "[page]"]
+#[sub_commands(season_leaderboard)]
+async fn leaderboard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let page: i64 = if args.is_empty() {
+        1
+    } else if let Ok(page) = args.single::<i64>() {
+        page
+    } else {
+        let response = "Usage: `leaderboard [page]` or `leaderboard season <number> [page]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let pool: PgPool = {
+        let rlock = ctx.data.read().await;
+        rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone()
+    };
+    send_page(ctx, &bot_cfg, &pool, msg, None, page).await
+}
+
+#[command("season")]
+#[only_in(guilds)]
+#[description = "Shows the final standings of an archived season."]
+#[usage = "<number> [page]"]
+async fn season_leaderboard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(season) = args.single::<i64>() else {
+        let response = "Usage: `leaderboard season <number> [page]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let page: i64 = if args.is_empty() {
+        1
+    } else if let Ok(page) = args.single::<i64>() {
+        page
+    } else {
+        let response = "Usage: `leaderboard season <number> [page]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+
+    let pool: PgPool = {
+        let rlock = ctx.data.read().await;
+        rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone()
+    };
+    if db::season(&pool, season).await?.is_none() {
+        let response = format!("There is no archived season {season}.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    send_page(ctx, &bot_cfg, &pool, msg, Some(season), page).await
+}
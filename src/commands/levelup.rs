@@ -0,0 +1,131 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::app_state::{
+    level_up::{LevelUpSetting, LevelUpSettings},
+    sync,
+    type_map_keys::{AppStateKey, PgPoolKey},
+    AppState,
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+#[command]
+#[only_in(guilds)]
+#[description = "Command set for the announcements of promotions to a higher earned role."]
+#[sub_commands(config, pings)]
+async fn levelup(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(
+        ctx,
+        &bot_cfg,
+        msg,
+        &args,
+        LEVELUP_COMMAND.options.sub_commands,
+    )
+    .await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Shows or changes the level-up announcements. \
+`enabled` (`on` or `off`) turns the announcements on or off, \
+`channel` is the channel for the announcements or `default` for the bot channel, \
+`template` is the text of the announcements, where `{member}`, `{role}`, `{exp}` and `{next}` \
+are replaced with the member, the reached role, their exp and the exp needed for the next role."]
+#[usage = "[<enabled | channel | template> <value>]"]
+async fn config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+
+    if args.is_empty() {
+        let response: String = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            let mut msg_builder = MessageBuilder::new();
+            msg_builder.push("Level-up announcement settings:\n");
+            for setting in app_state.level_up_settings.to_settings() {
+                msg_builder.push(format!("\t{setting}\n"));
+            }
+            msg_builder.push(format!(
+                "Placeholders: `{}`",
+                LevelUpSettings::PLACEHOLDERS.join("`, `")
+            ));
+            msg_builder.build()
+        };
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let Ok(name) = args.single::<String>() else {
+        let response = "Usage: `levelup config [<name> <value>]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    // The template can contain spaces, so the value is the rest of the message
+    let setting: LevelUpSetting = match LevelUpSetting::parse(&name, args.rest()) {
+        Ok(setting) => setting,
+        Err(problem) => return respond(ctx, &bot_cfg, msg, problem).await,
+    };
+    let updated: String = setting.to_string();
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_level_up_setting(&mut app_state.level_up_settings, &pool, setting).await?;
+    }
+
+    respond(ctx, &bot_cfg, msg, format!("Updated {updated}")).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Chooses whether the announcements of your promotions ping you."]
+#[usage = "<on | off>"]
+async fn pings(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let pings: bool = match args.single::<String>().as_deref() {
+        Ok("on" | "true" | "yes") => true,
+        Ok("off" | "false" | "no") => false,
+        _ => {
+            let response = "Usage: `levelup pings <on | off>`";
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_level_up_pings(
+            &mut app_state.level_up_ping_opt_outs,
+            &pool,
+            msg.author.id,
+            pings,
+        )
+        .await?;
+    }
+
+    let response = if pings {
+        "The announcements of your promotions will ping you."
+    } else {
+        "The announcements of your promotions won't ping you anymore."
+    };
+    respond(ctx, &bot_cfg, msg, response).await
+}
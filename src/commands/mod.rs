@@ -2,33 +2,69 @@ use std::collections::HashSet;
 
 use serenity::{
     async_trait,
+    builder::CreateEmbed,
     framework::standard::{
         help_commands,
         macros::{group, help},
-        Args, CommandError, CommandGroup, CommandResult, HelpOptions,
+        Args, Command, CommandError, CommandGroup, CommandResult, HelpOptions,
     },
     http::Http,
     model::prelude::{Message, UserId},
     prelude::Context,
+    utils::MessageBuilder,
 };
 
+mod earned_role;
+mod event;
+mod exp;
+pub(crate) mod leaderboard;
+mod levelup;
 mod ping;
+mod rank;
+mod rankcard;
 pub(crate) mod role;
+mod season;
+mod selfrole;
 mod sql;
 mod stop;
+mod streak;
 
+use event::EVENT_COMMAND;
+use exp::EXP_COMMAND;
+use leaderboard::LEADERBOARD_COMMAND;
+use levelup::LEVELUP_COMMAND;
 use ping::PING_COMMAND;
+use rank::RANK_COMMAND;
+use rankcard::RANKCARD_COMMAND;
 use role::ROLE_COMMAND;
+use season::SEASON_COMMAND;
+use selfrole::SELFROLE_COMMAND;
 use sql::SQL_COMMAND;
 use stop::STOP_COMMAND;
+use streak::STREAK_COMMAND;
 
 use crate::{
-    app_state::{EarnedRole, ServerMember},
+    app_state::{ladder::EarnedRoleMode, type_map_keys::BotCfgKey, EarnedRole, ServerMember},
+    immut_data::dynamic::BotCfg,
     MainBot,
 };
 
 #[group]
-#[commands(ping, role, sql, stop)]
+#[commands(
+    event,
+    exp,
+    leaderboard,
+    levelup,
+    ping,
+    rank,
+    rankcard,
+    role,
+    season,
+    selfrole,
+    sql,
+    stop,
+    streak
+)]
 struct General;
 
 #[async_trait]
@@ -39,10 +75,104 @@ pub(crate) trait Progress: Sized {
         http: &Http,
         sorted_earned_roles: &mut Vec<EarnedRole>,
         users: &mut Vec<ServerMember>,
+        earned_role_mode: EarnedRoleMode,
         msg: &Message,
     ) -> Result<Option<&mut Self>, CommandError>;
 }
 
+/// Returns a copy of the bot configuration stored in [`Context::data`].
+///
+/// Unlike a reference obtained via a read lock, the copy can be held
+/// while [`Context::data`] is locked for writing.
+pub(crate) async fn bot_cfg(ctx: &Context) -> BotCfg {
+    let rlock = ctx.data.read().await;
+    rlock
+        .get::<BotCfgKey>()
+        .expect("Failed to get the bot config from the typemap")
+        .clone()
+}
+
+/// Responds to the author of the message in the bot channel.
+///
+/// Only the author gets pinged by the response. If the message was sent
+/// outside of the bot channel, it gets deleted.
+pub(crate) async fn respond(
+    ctx: &Context,
+    bot_cfg: &BotCfg,
+    msg: &Message,
+    content: impl std::fmt::Display,
+) -> CommandResult {
+    let response = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(" ")
+        .push(content)
+        .build();
+    bot_cfg
+        .discord_bot_channel
+        .send_message(&ctx.http, |m| {
+            m.content(&response)
+                .allowed_mentions(|am| am.empty_parse().users([msg.author.id]))
+        })
+        .await?;
+    if msg.channel_id != bot_cfg.discord_bot_channel {
+        msg.delete(&ctx).await?;
+    }
+    Ok(())
+}
+
+/// Responds to the author of the message in the bot channel with an embed.
+///
+/// Behaves like [`respond`] otherwise.
+pub(crate) async fn respond_with_embed(
+    ctx: &Context,
+    bot_cfg: &BotCfg,
+    msg: &Message,
+    build_embed: impl FnOnce(&mut CreateEmbed) -> &mut CreateEmbed,
+) -> CommandResult {
+    let mention = MessageBuilder::new().mention(&msg.author).build();
+    bot_cfg
+        .discord_bot_channel
+        .send_message(&ctx.http, |m| {
+            m.content(&mention)
+                .embed(build_embed)
+                .allowed_mentions(|am| am.empty_parse().users([msg.author.id]))
+        })
+        .await?;
+    if msg.channel_id != bot_cfg.discord_bot_channel {
+        msg.delete(&ctx).await?;
+    }
+    Ok(())
+}
+
+/// Responds with the list of subcommands or complains about the unknown one.
+///
+/// Meant to be called by commands that only group subcommands together.
+pub(crate) async fn suggest_subcommands(
+    ctx: &Context,
+    bot_cfg: &BotCfg,
+    msg: &Message,
+    args: &Args,
+    subcommands: &[&'static Command],
+) -> CommandResult {
+    let mut msg_builder = MessageBuilder::new();
+    if let Some(actual_sub) = args.current() {
+        let actual_sub = actual_sub.replace('`', "");
+        msg_builder.push(format!("Unknown subcommand `{actual_sub}`"));
+    } else {
+        msg_builder.push("Try one of the following subcommands:\n");
+        for sub_name in subcommands
+            .iter()
+            .filter_map(|sub| sub.options.names.first())
+        {
+            msg_builder.push("\t");
+            msg_builder.push("`");
+            msg_builder.push(sub_name);
+            msg_builder.push("`");
+        }
+    };
+    respond(ctx, bot_cfg, msg, msg_builder.build()).await
+}
+
 // The framework provides two built-in help commands for you to use.
 // But you can also make your own customized help command that forwards
 // to the behaviour of either of them.
@@ -0,0 +1,115 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::{Member, Message, UserId},
+    prelude::Context,
+    utils::{Colour, MessageBuilder},
+};
+
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        rank::{Rank, RankRole},
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db::{self, dao},
+};
+
+use super::{bot_cfg, respond, respond_with_embed};
+
+/// The number of cells in the progress bar.
+const PROGRESS_BAR_LEN: usize = 20;
+
+fn progress_bar(progress: f64) -> String {
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    let filled = (progress * PROGRESS_BAR_LEN as f64).round() as usize;
+    let filled = filled.min(PROGRESS_BAR_LEN);
+    format!(
+        "{}{} {:.0}%",
+        "█".repeat(filled),
+        "░".repeat(PROGRESS_BAR_LEN - filled),
+        progress * 100.0
+    )
+}
+
+fn role_mention(role: Option<RankRole>) -> String {
+    match role {
+        Some(RankRole { role_id, .. }) => MessageBuilder::new().role(role_id).build(),
+        None => "none".to_string(),
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Shows the experience points of the member, their current earned role \
+and the progress towards the next one, along with their daily activity streak. \
+Without a member, shows your own rank."]
+#[usage = "[member]"]
+async fn rank(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let user_id: UserId = if args.is_empty() {
+        msg.author.id
+    } else if let Ok(user_id) = args.single::<UserId>() {
+        user_id
+    } else {
+        return respond(ctx, &bot_cfg, msg, "Usage: `rank [member]`").await;
+    };
+
+    let (rank, pool, timezone): (Option<Rank>, PgPool, String) = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let pool: PgPool = rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        (
+            app_state.rank(user_id),
+            pool,
+            app_state.streak_settings.timezone.clone(),
+        )
+    };
+    let Some(rank) = rank else {
+        let response = MessageBuilder::new()
+            .mention(&user_id)
+            .push(" has no rank on this server.")
+            .build();
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let member: Member = bot_cfg.discord_server_id.member(ctx, user_id).await?;
+    let streak: String = match db::streak(&pool, user_id, &timezone).await? {
+        Some(dao::Streak {
+            current_days,
+            longest_days,
+        }) => format!("{current_days} day(s), best {longest_days}"),
+        None => "0 day(s)".to_string(),
+    };
+
+    let Rank {
+        exp: exp_now,
+        earned_role,
+        next_role,
+    } = rank;
+    let exp_remaining: String = match rank.exp_remaining() {
+        Some(exp_remaining) => exp_remaining.0.to_string(),
+        None => "-".to_string(),
+    };
+    respond_with_embed(ctx, &bot_cfg, msg, |e| {
+        e.title(format!("{}'s rank", member.display_name()))
+            .thumbnail(member.face())
+            .colour(Colour::from_rgb(0xD2, 0x04, 0x2D))
+            .field("Exp", exp_now.0, true)
+            .field("Earned role", role_mention(earned_role), true)
+            .field("Next role", role_mention(next_role), true)
+            .field("Exp remaining", exp_remaining, true)
+            .field("Streak", streak, true)
+            .field("Progress", progress_bar(rank.progress()), false)
+    })
+    .await
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::{AttachmentType, Member, Message, Role, RoleId, UserId},
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        rank::{Rank, RankRole},
+        sync,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    rank_card::{
+        self,
+        theme::{Rgb, Theme, ThemeSetting},
+        RankCard,
+    },
+};
+
+use super::{bot_cfg, respond};
+
+#[command]
+#[only_in(guilds)]
+#[description = "Shows the rank of the member as an image. Without a member, shows your own rank."]
+#[usage = "[member]"]
+#[sub_commands(theme)]
+async fn rankcard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let user_id: UserId = if args.is_empty() {
+        msg.author.id
+    } else if let Ok(user_id) = args.single::<UserId>() {
+        user_id
+    } else {
+        return respond(ctx, &bot_cfg, msg, "Usage: `rankcard [member]`").await;
+    };
+
+    let (rank, theme): (Option<Rank>, Theme) = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        (app_state.rank(user_id), app_state.rank_card_theme)
+    };
+    let Some(rank) = rank else {
+        let response = MessageBuilder::new()
+            .mention(&user_id)
+            .push(" has no rank on this server.")
+            .build();
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let member: Member = bot_cfg.discord_server_id.member(ctx, user_id).await?;
+
+    let earned_role: Option<Role> = match rank.earned_role {
+        Some(RankRole { role_id, .. }) => {
+            let mut roles: HashMap<RoleId, Role> =
+                bot_cfg.discord_server_id.roles(&ctx.http).await?;
+            roles.remove(&role_id)
+        }
+        None => None,
+    };
+    let card = RankCard {
+        display_name: member.display_name().to_string(),
+        exp: rank.exp.0,
+        next_exp: rank.next_role.map(|r| r.exp_needed.0),
+        progress: rank.progress(),
+        earned_role_name: earned_role.as_ref().map(|r| r.name.clone()),
+        // Discord treats the color 0 as no color
+        earned_role_colour: earned_role
+            .as_ref()
+            .map(|r| r.colour.0)
+            .filter(|colour| *colour != 0)
+            .map(Rgb::from_u32),
+    };
+    let png: Vec<u8> = rank_card::render(&card, &theme)?;
+
+    let mention = MessageBuilder::new().mention(&msg.author).build();
+    bot_cfg
+        .discord_bot_channel
+        .send_message(&ctx.http, |m| {
+            m.content(&mention)
+                .add_file(AttachmentType::Bytes {
+                    data: png.into(),
+                    filename: "rank.png".to_string(),
+                })
+                .allowed_mentions(|am| am.empty_parse().users([msg.author.id]))
+        })
+        .await?;
+    if msg.channel_id != bot_cfg.discord_bot_channel {
+        msg.delete(&ctx).await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Shows or changes the look of the rank cards. \
+`layout` is either `wide` or `stacked`, `background`, `text`, `bar` and `accent` \
+are colors written as `#RRGGBB`."]
+#[usage = "[<layout | background | text | bar | accent> <value>]"]
+async fn theme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+
+    if args.is_empty() {
+        let response: String = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            let mut msg_builder = MessageBuilder::new();
+            msg_builder.push("Rank card theme:\n");
+            for setting in app_state.rank_card_theme.to_settings() {
+                msg_builder.push(format!("\t{setting}\n"));
+            }
+            msg_builder.build()
+        };
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let (Ok(name), Ok(value)) = (args.single::<String>(), args.single::<String>()) else {
+        let response = "Usage: `rankcard theme [<name> <value>]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let setting: ThemeSetting = match ThemeSetting::parse(&name, &value) {
+        Ok(setting) => setting,
+        Err(problem) => return respond(ctx, &bot_cfg, msg, problem).await,
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_theme_setting(&mut app_state.rank_card_theme, &pool, setting).await?;
+    }
+
+    respond(ctx, &bot_cfg, msg, format!("Updated {setting}")).await
+}
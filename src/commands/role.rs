@@ -4,6 +4,7 @@ use crate::{
     app_state::{
         self,
         exp::Exp,
+        ladder::{EarnedRoleChange, EarnedRoleMode},
         reqd_prompts::ReqdPrompts,
         type_map_keys::{AppStateKey, BotCfgKey},
         AppState,
@@ -20,11 +21,50 @@ use serenity::{
     utils::MessageBuilder,
 };
 
-use super::Progress;
+use super::{earned_role::EARNED_ROLES_COMMAND, Progress};
+
+const WHICH_ROLE_QUESTION: &str = "Which role do you want to add? \
+Mention an existing role, give its ID or name, or give the name of a new role.";
+
+/// The role that the earned role prompt is about.
+pub(crate) enum EarnedRoleTarget {
+    /// A role that already exists on the server.
+    Existing(Role),
+    /// A role that will be created once the prompt is complete.
+    New(String),
+}
+
+impl EarnedRoleTarget {
+    /// Resolves the answer to the first question of the prompt.
+    ///
+    /// The answer can be a role mention, a role ID, or the name of an existing role
+    /// (case-insensitive). Any other answer is the name of a new role.
+    async fn resolve(bot: &MainBot, http: &Http, answer: &str) -> Result<Self, CommandError> {
+        let answer = answer.trim();
+        let roles: HashMap<RoleId, Role> = bot.discord_server_id().roles(http).await?;
+        let role: Option<&Role> = match answer.parse::<RoleId>() {
+            Ok(role_id) => roles.get(&role_id),
+            Err(_) => roles
+                .values()
+                .find(|role| role.name.eq_ignore_ascii_case(answer)),
+        };
+        Ok(match role {
+            Some(role) => Self::Existing(role.clone()),
+            None => Self::New(answer.to_string()),
+        })
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::Existing(role) => &role.name,
+            Self::New(name) => name,
+        }
+    }
+}
 
 pub(crate) enum EarnedRolePromptProgress {
     JustStarted,
-    CollectedName(String),
+    CollectedRole(EarnedRoleTarget),
     // After collection of exp, the prompt is done
 }
 
@@ -36,6 +76,7 @@ impl Progress for EarnedRolePromptProgress {
         http: &Http,
         sorted_earned_roles: &mut Vec<app_state::EarnedRole>,
         users: &mut Vec<app_state::ServerMember>,
+        earned_role_mode: EarnedRoleMode,
         msg: &Message,
     ) -> Result<Option<&mut Self>, CommandError> {
         let mut msg_builder = MessageBuilder::new();
@@ -53,41 +94,127 @@ impl Progress for EarnedRolePromptProgress {
 
         let ret = match self {
             Self::JustStarted => {
-                let collected_name = msg.content.clone();
-                msg_builder.push("The collected name for the role is: ");
-                msg_builder.push(collected_name.as_str());
-                msg_builder.push("\n\n");
-                msg_builder.push(
-                    "The corresponding role will be added once all necessary info is available. ",
-                );
-                msg_builder.push("How much exp is needed for attaining the earned role?");
-                *self = Self::CollectedName(collected_name);
-                Some(self)
-            }
-            Self::CollectedName(name) => {
-                let role = bot
-                    .discord_server_id()
-                    .create_role(http, |r| r.name(&name))
-                    .await?;
-                // TODO: in case of error, repeat the prompt
-                let exp_needed: Exp = match msg.content.parse::<u64>() {
-                    Ok(exp_needed) => Exp(exp_needed),
-                    Err(_) => {
-                        return Err(CommandError::from("Failed to parse the exp_needed value"))
+                let target = EarnedRoleTarget::resolve(bot, http, &msg.content).await?;
+                match &target {
+                    EarnedRoleTarget::Existing(role)
+                        if role.id.0 == bot.discord_server_id().0 || role.managed =>
+                    {
+                        msg_builder.push_safe(format!(
+                            "The role {} can't be given to members by the bot. ",
+                            role.name
+                        ));
+                        msg_builder.push("Please name another role.");
+                        Some(self)
+                    }
+                    EarnedRoleTarget::Existing(role)
+                        if sorted_earned_roles.iter().any(|r| r.role_id() == role.id) =>
+                    {
+                        msg_builder.push_safe(format!(
+                            "The role {} is already an earned role. ",
+                            role.name
+                        ));
+                        msg_builder.push("Please name another role.");
+                        Some(self)
+                    }
+                    EarnedRoleTarget::New(name) if name.is_empty() => {
+                        msg_builder.push("The name of the role can't be empty. ");
+                        msg_builder.push(WHICH_ROLE_QUESTION);
+                        Some(self)
+                    }
+                    EarnedRoleTarget::Existing(role) => {
+                        msg_builder.push_safe(format!(
+                            "The existing role {} will become an earned role.",
+                            role.name
+                        ));
+                        msg_builder.push("\n\n");
+                        msg_builder.push("How much exp is needed for attaining the earned role?");
+                        *self = Self::CollectedRole(target);
+                        Some(self)
                     }
+                    EarnedRoleTarget::New(name) => {
+                        msg_builder.push("The collected name for the role is: ");
+                        msg_builder.push_safe(name.as_str());
+                        msg_builder.push("\n\n");
+                        msg_builder.push(
+                            "The corresponding role will be added once all necessary info is \
+                            available. ",
+                        );
+                        msg_builder.push("How much exp is needed for attaining the earned role?");
+                        *self = Self::CollectedRole(target);
+                        Some(self)
+                    }
+                }
+            }
+            Self::CollectedRole(target) => {
+                let exp_needed: Option<Exp> = msg.content.trim().parse::<u64>().ok().map(Exp);
+                let existing_id: Option<RoleId> = match target {
+                    EarnedRoleTarget::Existing(role) => Some(role.id),
+                    EarnedRoleTarget::New(_) => None,
                 };
-                app_state::sync::add_earned_role(
-                    http,
-                    &bot.cfg,
-                    sorted_earned_roles,
-                    users,
-                    &bot.pool,
-                    role.id,
-                    exp_needed,
-                )
-                .await?;
-                msg_builder.push(&format!("The earned role {name} has been added."));
-                None
+                let taken_by: Option<RoleId> = exp_needed.and_then(|exp_needed| {
+                    app_state::role_with_threshold(sorted_earned_roles, exp_needed, existing_id)
+                });
+                if let Some(taken_by) = taken_by {
+                    let taken_by_name: String = bot
+                        .discord_server_id()
+                        .roles(http)
+                        .await?
+                        .remove(&taken_by)
+                        .map_or_else(|| taken_by.to_string(), |role| role.name);
+                    msg_builder.push("The earned role ");
+                    msg_builder.push_safe(taken_by_name);
+                    msg_builder.push(" already needs this amount of exp. ");
+                    msg_builder.push("How much exp is needed for attaining the earned role?");
+                    Some(self)
+                } else if let Some(exp_needed) = exp_needed {
+                    let (role_id, is_created): (RoleId, bool) = match target {
+                        EarnedRoleTarget::Existing(role) => (role.id, false),
+                        EarnedRoleTarget::New(name) => {
+                            let role = bot
+                                .discord_server_id()
+                                .create_role(http, |r| r.name(&name))
+                                .await?;
+                            (role.id, true)
+                        }
+                    };
+                    // The roles are fetched first so that nothing is saved if it fails
+                    let res = match app_state::sync::fetch_member_roles(http, &bot.cfg).await {
+                        Ok(member_roles) => {
+                            app_state::sync::add_earned_role(
+                                sorted_earned_roles,
+                                users,
+                                earned_role_mode,
+                                &bot.pool,
+                                &member_roles,
+                                app_state::EarnedRole::new(role_id, exp_needed),
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let changes: Vec<EarnedRoleChange> = match res {
+                        Ok(changes) => changes,
+                        Err(e) => {
+                            // The role created for the prompt would be left unused
+                            if is_created {
+                                if let Err(e) =
+                                    bot.discord_server_id().delete_role(http, role_id).await
+                                {
+                                    eprintln!("Failed to delete the unused role {role_id}: {e}");
+                                }
+                            }
+                            return Err(e.into());
+                        }
+                    };
+                    app_state::sync::apply_earned_role_changes(http, &bot.cfg, &changes).await;
+                    msg_builder
+                        .push_safe(format!("The earned role {} has been added.", target.name()));
+                    None
+                } else {
+                    msg_builder.push("The amount of exp must be a non-negative whole number. ");
+                    msg_builder.push("How much exp is needed for attaining the earned role?");
+                    Some(self)
+                }
             }
         };
         bot.discord_bot_channel()
@@ -126,6 +253,7 @@ impl EarnedRolePromptReq {
         sorted_earned_roles: &mut Vec<app_state::EarnedRole>,
         reqd_prompts: &mut ReqdPrompts,
         users: &mut Vec<app_state::ServerMember>,
+        earned_role_mode: EarnedRoleMode,
     ) -> ControlFlow<()> {
         if let Some((i, req)) = reqd_prompts
             .earned_role
@@ -133,14 +261,25 @@ impl EarnedRolePromptReq {
             .enumerate()
             .find(|(_i, req)| req.discord_id == msg.author.id)
         {
-            match req
+            let res = req
                 .progress
-                .advance(bot, &ctx.http, sorted_earned_roles, users, msg)
-                .await
-                .unwrap()
-            {
-                Some(_req) => (),
-                None => {
+                .advance(
+                    bot,
+                    &ctx.http,
+                    sorted_earned_roles,
+                    users,
+                    earned_role_mode,
+                    msg,
+                )
+                .await;
+            match res {
+                Ok(Some(_req)) => (),
+                Ok(None) => {
+                    reqd_prompts.earned_role.remove(i);
+                }
+                // The prompt is dropped so that the member isn't stuck with it
+                Err(e) => {
+                    eprintln!("Failed to advance the earned role prompt: {e}");
                     reqd_prompts.earned_role.remove(i);
                 }
             };
@@ -152,7 +291,7 @@ impl EarnedRolePromptReq {
 
 #[command]
 #[description = "Role command set."]
-#[sub_commands(ids, add)]
+#[sub_commands(ids, add, earned_roles)]
 async fn role(ctx: &Context, msg: &Message) -> CommandResult {
     let subcommands = ROLE_COMMAND_OPTIONS.sub_commands;
     let rlock = ctx.data.read().await;
@@ -299,7 +438,7 @@ async fn earned(ctx: &Context, msg: &Message) -> CommandResult {
     let mut msg_builder = MessageBuilder::new();
     msg_builder.mention(&msg.author);
     msg_builder.push(" ");
-    msg_builder.push("What's the name of the role that you want to add?");
+    msg_builder.push(WHICH_ROLE_QUESTION);
 
     {
         let mut wlock = ctx.data.write().await;
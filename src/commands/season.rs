@@ -0,0 +1,225 @@
+use std::fmt::Write;
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::{AttachmentType, Message},
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        ladder::EarnedRoleChange,
+        season::SeasonSetting,
+        sync,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db::{self, dao},
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+#[command]
+#[only_in(guilds)]
+#[description = "Command set for the seasons, after which the exp is reset \
+and the final standings are archived."]
+#[sub_commands(list, config, end, export)]
+async fn season(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(
+        ctx,
+        &bot_cfg,
+        msg,
+        &args,
+        SEASON_COMMAND.options.sub_commands,
+    )
+    .await
+}
+
+async fn pool(ctx: &Context) -> PgPool {
+    let rlock = ctx.data.read().await;
+    rlock
+        .get::<PgPoolKey>()
+        .expect("Failed to get the database pool from the typemap")
+        .clone()
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Lists the archived seasons. \
+Their standings are shown by `leaderboard season <number>`."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let seasons: Vec<dao::Season> = db::seasons(&pool(ctx).await).await?;
+
+    if seasons.is_empty() {
+        return respond(ctx, &bot_cfg, msg, "No season has ended yet.").await;
+    }
+    let mut msg_builder = MessageBuilder::new();
+    msg_builder.push("Archived seasons:\n");
+    for dao::Season {
+        number,
+        started_on,
+        ended_on,
+        kept_percent,
+        member_count,
+    } in seasons
+    {
+        msg_builder.push(format!(
+            "\tSeason {number}: {started_on} – {ended_on}, {member_count} member(s), \
+            {kept_percent}% of the exp kept\n"
+        ));
+    }
+    respond(ctx, &bot_cfg, msg, msg_builder.build()).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Shows or changes the season settings. \
+`length` is the number of days after which a season ends on its own \
+(`0` means that seasons end only with `season end`), \
+`keep` is the percentage of the exp that the members keep into the next season \
+(`0` resets the exp)."]
+#[usage = "[<length | keep> <value>]"]
+async fn config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+
+    if args.is_empty() {
+        let response: String = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            let mut msg_builder = MessageBuilder::new();
+            msg_builder.push("Season settings:\n");
+            for setting in app_state.season_settings.to_settings() {
+                msg_builder.push(format!("\t{setting}\n"));
+            }
+            msg_builder.build()
+        };
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let (Ok(name), Ok(value)) = (args.single::<String>(), args.single::<String>()) else {
+        let response = "Usage: `season config [<name> <value>]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let setting: SeasonSetting = match SeasonSetting::parse(&name, &value) {
+        Ok(setting) => setting,
+        Err(problem) => return respond(ctx, &bot_cfg, msg, problem).await,
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_season_setting(&mut app_state.season_settings, &pool, setting).await?;
+    }
+
+    respond(ctx, &bot_cfg, msg, format!("Updated {setting}")).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Ends the current season. The standings are archived, the members keep \
+the configured part of their exp and their earned roles are updated. \
+Needs `confirm` since it can't be undone."]
+#[usage = "confirm"]
+async fn end(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    if !matches!(args.single::<String>().as_deref(), Ok("confirm")) {
+        let kept_percent: u32 = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            app_state.season_settings.kept_percent
+        };
+        let response = format!(
+            "Ending the season archives the standings and leaves every member \
+            with {kept_percent}% of their exp. It can't be undone. \
+            Use `season end confirm` to proceed."
+        );
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let member_roles = sync::fetch_member_roles(&ctx.http, &bot_cfg).await?;
+    let (number, changes): (i64, Vec<EarnedRoleChange>) = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::end_season(app_state, &pool, &member_roles).await?
+    };
+    sync::apply_earned_role_changes(&ctx.http, &bot_cfg, &changes).await;
+
+    let response = format!(
+        "Season {number} has ended and its standings were archived. \
+        The earned roles of {} member(s) were updated.",
+        changes.len()
+    );
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Exports the final standings of an archived season as a CSV file."]
+#[usage = "<number>"]
+async fn export(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(number) = args.single::<i64>() else {
+        let response = "Usage: `season export <number>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let pool: PgPool = pool(ctx).await;
+    if db::season(&pool, number).await?.is_none() {
+        let response = format!("There is no archived season {number}.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    let mut csv = String::from("position,discord_id,exp,earned_role_id\n");
+    for dao::SeasonStanding {
+        position,
+        discord_id,
+        exp,
+        earned_role_id,
+    } in db::season_standings(&pool, number).await?
+    {
+        let earned_role_id: String = earned_role_id.map_or_else(String::new, |id| id.to_string());
+        writeln!(csv, "{position},{discord_id},{exp},{earned_role_id}")?;
+    }
+
+    let content = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(format!(" The final standings of season {number}:"))
+        .build();
+    bot_cfg
+        .discord_bot_channel
+        .send_message(&ctx.http, |m| {
+            m.content(&content)
+                .add_file(AttachmentType::Bytes {
+                    data: csv.into_bytes().into(),
+                    filename: format!("season_{number}.csv"),
+                })
+                .allowed_mentions(|am| am.empty_parse().users([msg.author.id]))
+        })
+        .await?;
+    if msg.channel_id != bot_cfg.discord_bot_channel {
+        msg.delete(&ctx).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+
+use serenity::{
+    builder::CreateComponents,
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{
+        application::component::ButtonStyle,
+        prelude::{Message, MessageId, ReactionType, Role, RoleId},
+    },
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        roles::{Presentation, SelfRoleEntry},
+        sync,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db,
+    immut_data::{
+        consts::{SELF_ROLE_CHOICE_SUFFIX, SELF_ROLE_CUSTOM_ID_PREFIX},
+        dynamic::BotCfg,
+    },
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+/// Attaches the buttons, or the dropdown for a choice group, to the self-role message
+/// so that they reflect its self-assignable roles.
+///
+/// Only messages posted by Vampy can have components.
+async fn render_components(
+    ctx: &Context,
+    bot_cfg: &BotCfg,
+    message_id: MessageId,
+) -> CommandResult {
+    // Discord allows at most 5 buttons in a row
+    const BUTTONS_PER_ROW: usize = 5;
+
+    let (entries, is_choice_group): (Vec<SelfRoleEntry>, bool) = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        (
+            app_state.self_role_msgs.msg_entries(message_id),
+            app_state.self_role_msgs.is_choice_group(message_id),
+        )
+    };
+    let roles: HashMap<RoleId, Role> = bot_cfg.discord_server_id.roles(&ctx.http).await?;
+    let role_name = |role_id: &RoleId| -> String {
+        roles
+            .get(role_id)
+            .map_or_else(|| role_id.to_string(), |r| r.name.clone())
+    };
+
+    let mut components = CreateComponents::default();
+    if entries.is_empty() {
+        // No components are attached
+    } else if is_choice_group {
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(format!(
+                    "{SELF_ROLE_CUSTOM_ID_PREFIX}{SELF_ROLE_CHOICE_SUFFIX}"
+                ))
+                .placeholder("Pick a role")
+                .min_values(0)
+                .max_values(1)
+                .options(|opts| {
+                    for entry in &entries {
+                        opts.create_option(|o| {
+                            o.label(role_name(&entry.role_id))
+                                .value(entry.role_id)
+                                .emoji(entry.reaction.clone())
+                        });
+                    }
+                    opts
+                })
+            })
+        });
+    } else {
+        for chunk in entries.chunks(BUTTONS_PER_ROW) {
+            components.create_action_row(|row| {
+                for entry in chunk {
+                    row.create_button(|b| {
+                        b.custom_id(format!("{SELF_ROLE_CUSTOM_ID_PREFIX}{}", entry.role_id))
+                            .style(ButtonStyle::Secondary)
+                            .label(role_name(&entry.role_id))
+                            .emoji(entry.reaction.clone())
+                    });
+                }
+                row
+            });
+        }
+    }
+
+    bot_cfg
+        .discord_self_role_channel
+        .edit_message(&ctx.http, message_id, |m| m.set_components(components))
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Command set for managing self-role messages."]
+#[sub_commands(create, edit, add, remove, group, present, delete, list)]
+async fn selfrole(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let subcommands = SELFROLE_COMMAND_OPTIONS.sub_commands;
+    suggest_subcommands(ctx, &bot_cfg, msg, &args, subcommands).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Posts a new self-role message in the self-role channel."]
+#[usage = "<text>"]
+async fn create(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let text = args.rest().trim();
+    if text.is_empty() {
+        return respond(ctx, &bot_cfg, msg, "The self-role message needs some text.").await;
+    }
+    let self_role_msg = bot_cfg
+        .discord_self_role_channel
+        .send_message(&ctx.http, |m| {
+            m.content(text).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+    let message_id = self_role_msg.id;
+    let prefix = &bot_cfg.discord_prefix;
+    let response = format!(
+        "The self-role message `{message_id}` has been posted. \
+        Attach roles to it via `{prefix}selfrole add {message_id} <role> <emoji> [group]`."
+    );
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Replaces the text of a self-role message posted by Vampy."]
+#[usage = "<message id> <text>"]
+async fn edit(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(message_id) = args.single::<u64>() else {
+        return respond(
+            ctx,
+            &bot_cfg,
+            msg,
+            "Usage: `selfrole edit <message id> <text>`",
+        )
+        .await;
+    };
+    let text = args.rest().trim();
+    if text.is_empty() {
+        return respond(ctx, &bot_cfg, msg, "The self-role message needs some text.").await;
+    }
+    bot_cfg
+        .discord_self_role_channel
+        .edit_message(&ctx.http, MessageId(message_id), |m| m.content(text))
+        .await?;
+    let response = format!("The self-role message `{message_id}` has been edited.");
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Makes a role self-assignable via a self-role message. \
+Roles sharing a group are mutually exclusive. If the group is omitted, a new one is used."]
+#[usage = "<message id> <role> <emoji> [group]"]
+async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    const USAGE: &str = "Usage: `selfrole add <message id> <role> <emoji> [group]`";
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(message_id), Ok(role_id), Ok(emoji)) = (
+        args.single::<u64>(),
+        args.single::<RoleId>(),
+        args.single::<ReactionType>(),
+    ) else {
+        return respond(ctx, &bot_cfg, msg, USAGE).await;
+    };
+    let message_id = MessageId(message_id);
+    let excl_role_group_id: Option<i64> = if args.is_empty() {
+        None
+    } else if let Ok(excl_role_group_id) = args.single::<i64>() {
+        Some(excl_role_group_id)
+    } else {
+        return respond(ctx, &bot_cfg, msg, USAGE).await;
+    };
+
+    let Ok(self_role_msg) = bot_cfg
+        .discord_self_role_channel
+        .message(&ctx.http, message_id)
+        .await
+    else {
+        let response =
+            format!("Couldn't find the message `{message_id}` in the self-role channel.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    let roles: HashMap<RoleId, Role> = bot_cfg.discord_server_id.roles(&ctx.http).await?;
+    if !roles.contains_key(&role_id) {
+        let response = format!("Couldn't find the role `{role_id}` on the server.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    let presentation: Presentation = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        if let Some(SelfRoleEntry { message_id, .. }) = app_state.self_role_msgs.entry(role_id) {
+            let response = format!(
+                "The role is already self-assignable via the message `{message_id}`. \
+                Remove it first if you want to move it."
+            );
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+        if app_state
+            .self_role_msgs
+            .role_for_reaction(message_id, &emoji)
+            .is_some()
+        {
+            let response = format!("The emoji {emoji} is already taken on this message.");
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+        let presentation: Presentation = app_state
+            .self_role_msgs
+            .presentation(message_id)
+            .unwrap_or_default();
+        let max_roles: usize = presentation.max_roles();
+        if app_state.self_role_msgs.msg_entries(message_id).len() >= max_roles {
+            let response = format!(
+                "A self-role message can offer at most {max_roles} roles via {}.",
+                presentation.as_str()
+            );
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+        presentation
+    };
+    if presentation == Presentation::Reactions {
+        if let Err(e) = self_role_msg.react(&ctx, emoji.clone()).await {
+            let response = format!("Couldn't react with {emoji}. Is it an emoji from this server?");
+            eprintln!("Failed to react to the self-role message: {e}");
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+    }
+
+    let excl_role_group_id: i64 = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let res = async {
+            let excl_role_group_id = match excl_role_group_id {
+                Some(excl_role_group_id) => excl_role_group_id,
+                None => db::new_excl_role_group_id(&pool).await?,
+            };
+            sync::add_self_role(
+                &mut app_state.self_role_msgs,
+                &pool,
+                excl_role_group_id,
+                role_id,
+                message_id,
+                &emoji,
+                presentation,
+            )
+            .await?;
+            Ok::<i64, sqlx::Error>(excl_role_group_id)
+        }
+        .await;
+        match res {
+            Ok(excl_role_group_id) => excl_role_group_id,
+            Err(e) => {
+                if presentation == Presentation::Reactions {
+                    self_role_msg
+                        .channel_id
+                        .delete_reaction(&ctx.http, message_id, None, emoji)
+                        .await?;
+                }
+                return Err(e.into());
+            }
+        }
+    };
+    if presentation == Presentation::Components {
+        render_components(ctx, &bot_cfg, message_id).await?;
+    }
+
+    let response = MessageBuilder::new()
+        .push("Choosing ")
+        .push(&emoji)
+        .push(" on the message `")
+        .push(message_id)
+        .push("` now grants ")
+        .role(role_id)
+        .push(format!(" (group `{excl_role_group_id}`)."))
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Makes a role no longer self-assignable and clears its reactions or its button."]
+#[usage = "<role>"]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(role_id) = args.single::<RoleId>() else {
+        return respond(ctx, &bot_cfg, msg, "Usage: `selfrole remove <role>`").await;
+    };
+
+    let entry: SelfRoleEntry = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let Some(entry) = app_state.self_role_msgs.entry(role_id) else {
+            drop(wlock);
+            return respond(ctx, &bot_cfg, msg, "The role is not self-assignable.").await;
+        };
+        sync::remove_self_role(&mut app_state.self_role_msgs, &pool, role_id).await?;
+        entry
+    };
+
+    match entry.presentation {
+        Presentation::Reactions => {
+            if let Err(e) = bot_cfg
+                .discord_self_role_channel
+                .delete_reaction_emoji(&ctx.http, entry.message_id, entry.reaction)
+                .await
+            {
+                eprintln!("Failed to clear the reactions of the removed self-role: {e}");
+            }
+        }
+        Presentation::Components => {
+            render_components(ctx, &bot_cfg, entry.message_id).await?;
+        }
+    }
+
+    let response = MessageBuilder::new()
+        .role(role_id)
+        .push(" is no longer self-assignable.")
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Moves a self-assignable role to another group of mutually exclusive roles."]
+#[usage = "<role> <group | new>"]
+async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    const USAGE: &str = "Usage: `selfrole group <role> <group | new>`";
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(role_id), Ok(group)) = (args.single::<RoleId>(), args.single::<String>()) else {
+        return respond(ctx, &bot_cfg, msg, USAGE).await;
+    };
+
+    let excl_role_group_id: i64 = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        if app_state.self_role_msgs.entry(role_id).is_none() {
+            drop(wlock);
+            return respond(ctx, &bot_cfg, msg, "The role is not self-assignable.").await;
+        }
+        let excl_role_group_id = if group == "new" {
+            db::new_excl_role_group_id(&pool).await?
+        } else if let Ok(excl_role_group_id) = group.parse::<i64>() {
+            excl_role_group_id
+        } else {
+            drop(wlock);
+            return respond(ctx, &bot_cfg, msg, USAGE).await;
+        };
+        sync::set_excl_role_group(
+            &mut app_state.self_role_msgs,
+            &pool,
+            role_id,
+            excl_role_group_id,
+        )
+        .await?;
+        excl_role_group_id
+    };
+
+    let response = MessageBuilder::new()
+        .role(role_id)
+        .push(format!(" now belongs to the group `{excl_role_group_id}`."))
+        .build();
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Changes the way a self-role message offers its roles: \
+via reactions or via buttons (a dropdown for mutually exclusive roles)."]
+#[usage = "<message id> <reactions | components>"]
+async fn present(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    const USAGE: &str = "Usage: `selfrole present <message id> <reactions | components>`";
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(message_id), Ok(presentation)) = (args.single::<u64>(), args.single::<String>()) else {
+        return respond(ctx, &bot_cfg, msg, USAGE).await;
+    };
+    let message_id = MessageId(message_id);
+    let Ok(presentation) = presentation.parse::<Presentation>() else {
+        return respond(ctx, &bot_cfg, msg, USAGE).await;
+    };
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        if app_state.self_role_msgs.presentation(message_id).is_none() {
+            drop(wlock);
+            let response = format!("The message `{message_id}` has no self-assignable roles.");
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+        let max_roles: usize = presentation.max_roles();
+        if app_state.self_role_msgs.msg_entries(message_id).len() > max_roles {
+            drop(wlock);
+            let response = format!(
+                "The message `{message_id}` offers more than {max_roles} roles, \
+                which is too many for {}.",
+                presentation.as_str()
+            );
+            return respond(ctx, &bot_cfg, msg, response).await;
+        }
+        sync::set_self_role_msg_presentation(
+            &mut app_state.self_role_msgs,
+            &pool,
+            message_id,
+            presentation,
+        )
+        .await?;
+    }
+
+    let mut self_role_msg = bot_cfg
+        .discord_self_role_channel
+        .message(&ctx.http, message_id)
+        .await?;
+    match presentation {
+        Presentation::Reactions => {
+            if !self_role_msg.components.is_empty() {
+                self_role_msg
+                    .edit(&ctx, |m| m.set_components(CreateComponents::default()))
+                    .await?;
+            }
+            let entries: Vec<SelfRoleEntry> = {
+                let rlock = ctx.data.read().await;
+                let app_state: &AppState = rlock
+                    .get::<AppStateKey>()
+                    .expect("Failed to get the app state from the typemap");
+                app_state.self_role_msgs.msg_entries(message_id)
+            };
+            for entry in entries {
+                self_role_msg.react(&ctx, entry.reaction).await?;
+            }
+        }
+        Presentation::Components => {
+            render_components(ctx, &bot_cfg, message_id).await?;
+            self_role_msg.delete_reactions(&ctx).await?;
+        }
+    }
+
+    let response = format!(
+        "The message `{message_id}` now offers its roles via {}.",
+        presentation.as_str()
+    );
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Deletes a self-role message and makes its roles no longer self-assignable."]
+#[usage = "<message id>"]
+async fn delete(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let Ok(message_id) = args.single::<u64>() else {
+        return respond(ctx, &bot_cfg, msg, "Usage: `selfrole delete <message id>`").await;
+    };
+    let message_id = MessageId(message_id);
+
+    let is_tracked: bool = {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let is_tracked = app_state
+            .self_role_msgs
+            .entries()
+            .any(|entry| entry.message_id == message_id);
+        if is_tracked {
+            sync::remove_self_role_msg(&mut app_state.self_role_msgs, &pool, message_id).await?;
+        }
+        is_tracked
+    };
+    if !is_tracked {
+        let response = format!("`{message_id}` is not a self-role message.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    if let Err(e) = bot_cfg
+        .discord_self_role_channel
+        .delete_message(&ctx.http, message_id)
+        .await
+    {
+        eprintln!("Failed to delete the self-role message: {e}");
+    }
+
+    let response = format!("The self-role message `{message_id}` has been deleted.");
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "See the list of self-assignable roles."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let response: String = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        let mut msg_builder = MessageBuilder::new();
+        msg_builder.push("Self-assignable roles:\n");
+        let mut last_message_id: Option<MessageId> = None;
+        for SelfRoleEntry {
+            message_id,
+            role_id,
+            reaction,
+            excl_role_group_id,
+            presentation,
+        } in app_state.self_role_msgs.entries()
+        {
+            if last_message_id != Some(message_id) {
+                msg_builder.push(format!(
+                    "Message `{message_id}` ({}):\n",
+                    presentation.as_str()
+                ));
+                last_message_id = Some(message_id);
+            }
+            msg_builder
+                .push("\t")
+                .push(reaction)
+                .push(" ")
+                .role(role_id)
+                .push(format!(" (group `{excl_role_group_id}`)\n"));
+        }
+        msg_builder.build()
+    };
+    respond(ctx, &bot_cfg, msg, response).await
+}
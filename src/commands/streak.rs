@@ -0,0 +1,151 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+    utils::MessageBuilder,
+};
+use sqlx::PgPool;
+
+use crate::{
+    app_state::{
+        exp::Exp,
+        sync,
+        type_map_keys::{AppStateKey, PgPoolKey},
+        AppState,
+    },
+    db,
+};
+
+use super::{bot_cfg, respond, suggest_subcommands};
+
+#[command]
+#[only_in(guilds)]
+#[description = "Command set for the daily activity streaks, the runs of consecutive days \
+on which a member posted at least one message that earned exp."]
+#[sub_commands(bonuses, bonus, timezone)]
+async fn streak(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    suggest_subcommands(
+        ctx,
+        &bot_cfg,
+        msg,
+        &args,
+        STREAK_COMMAND.options.sub_commands,
+    )
+    .await
+}
+
+#[command]
+#[only_in(guilds)]
+#[description = "Lists the bonus exp for reaching the streak milestones."]
+async fn bonuses(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let bonuses: Vec<(u32, Exp)> = {
+        let rlock = ctx.data.read().await;
+        let app_state: &AppState = rlock
+            .get::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        app_state
+            .streak_settings
+            .bonuses
+            .iter()
+            .map(|(days, bonus)| (*days, *bonus))
+            .collect()
+    };
+
+    if bonuses.is_empty() {
+        return respond(ctx, &bot_cfg, msg, "There are no streak bonuses.").await;
+    }
+    let mut msg_builder = MessageBuilder::new();
+    msg_builder.push("Streak bonuses:\n");
+    for (days, Exp(bonus)) in bonuses {
+        msg_builder.push(format!("\t{days} day(s): {bonus} exp\n"));
+    }
+    respond(ctx, &bot_cfg, msg, msg_builder.build()).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Sets the bonus exp for reaching a streak of the given number of days. \
+A bonus of 0 removes it."]
+#[usage = "<days> <exp>"]
+async fn bonus(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+    let (Ok(days), Ok(bonus)) = (args.single::<u32>(), args.single::<u64>()) else {
+        let response = "Usage: `streak bonus <days> <exp>`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+    if days == 0 {
+        let response = "A streak is at least 1 day long.";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    let bonus: Option<Exp> = (bonus > 0).then_some(Exp(bonus));
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let pool: PgPool = wlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone();
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_streak_bonus(&mut app_state.streak_settings, &pool, days, bonus).await?;
+    }
+
+    let response = match bonus {
+        Some(Exp(bonus)) => format!("A streak of {days} day(s) now earns {bonus} bonus exp."),
+        None => format!("A streak of {days} day(s) no longer earns bonus exp."),
+    };
+    respond(ctx, &bot_cfg, msg, response).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Shows or changes the timezone in which the days of the streaks start and end. \
+The timezone is an IANA name, such as `Europe/Warsaw` or `UTC`."]
+#[usage = "[timezone]"]
+async fn timezone(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_cfg = bot_cfg(ctx).await;
+
+    if args.is_empty() {
+        let timezone: String = {
+            let rlock = ctx.data.read().await;
+            let app_state: &AppState = rlock
+                .get::<AppStateKey>()
+                .expect("Failed to get the app state from the typemap");
+            app_state.streak_settings.timezone.clone()
+        };
+        let response = format!("The days of the streaks are counted in `{timezone}`.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+    let Ok(timezone) = args.single::<String>() else {
+        let response = "Usage: `streak timezone [timezone]`";
+        return respond(ctx, &bot_cfg, msg, response).await;
+    };
+
+    let pool: PgPool = {
+        let rlock = ctx.data.read().await;
+        rlock
+            .get::<PgPoolKey>()
+            .expect("Failed to get the database pool from the typemap")
+            .clone()
+    };
+    if !db::is_timezone(&pool, &timezone).await? {
+        let response = format!("`{timezone}` is not a known timezone.");
+        return respond(ctx, &bot_cfg, msg, response).await;
+    }
+
+    {
+        let mut wlock = ctx.data.write().await;
+        let app_state: &mut AppState = wlock
+            .get_mut::<AppStateKey>()
+            .expect("Failed to get the app state from the typemap");
+        sync::set_streak_timezone(&mut app_state.streak_settings, &pool, timezone.clone()).await?;
+    }
+
+    let response = format!("The days of the streaks are now counted in `{timezone}`.");
+    respond(ctx, &bot_cfg, msg, response).await
+}
@@ -31,4 +31,94 @@ pub(crate) struct SelfAssignedRole {
     pub(crate) message_id: i64,
     pub(crate) emoji_id: Option<i64>,
     pub(crate) emoji_name: Option<String>,
+    pub(crate) presentation: String,
+}
+
+#[derive(FromRow)]
+pub(crate) struct ExpSettings {
+    pub(crate) msg_cooldown_secs: i64,
+    pub(crate) min_msg_chars: i64,
+    pub(crate) ignore_duplicate_msgs: bool,
+    pub(crate) voice_exp_per_min: i64,
+    pub(crate) decay_after_days: i64,
+    pub(crate) decay_percent: i64,
+    pub(crate) decay_period_days: i64,
+    pub(crate) decay_floor: i64,
+}
+
+#[derive(FromRow)]
+pub(crate) struct ChannelExpMultiplier {
+    pub(crate) channel_id: i64,
+    pub(crate) multiplier: f64,
+}
+
+#[derive(FromRow)]
+pub(crate) struct RoleExpMultiplier {
+    pub(crate) role_id: i64,
+    pub(crate) multiplier: f64,
+}
+
+#[derive(FromRow)]
+pub(crate) struct RankCardTheme {
+    pub(crate) layout: String,
+    pub(crate) background: i32,
+    pub(crate) text: i32,
+    pub(crate) bar: i32,
+    pub(crate) accent: i32,
+}
+
+#[derive(FromRow)]
+pub(crate) struct LevelUpSettings {
+    pub(crate) enabled: bool,
+    pub(crate) channel_id: Option<i64>,
+    pub(crate) template: String,
+}
+
+#[derive(FromRow)]
+pub(crate) struct SeasonSettings {
+    pub(crate) length_days: i64,
+    pub(crate) kept_percent: i64,
+}
+
+/// An archived season. The times are formatted by the database.
+#[derive(FromRow, Debug)]
+pub(crate) struct Season {
+    pub(crate) number: i64,
+    pub(crate) started_on: String,
+    pub(crate) ended_on: String,
+    pub(crate) kept_percent: i64,
+    pub(crate) member_count: i64,
+}
+
+/// The final standing of a member in an archived season.
+#[derive(FromRow, Debug, Clone, Copy)]
+pub(crate) struct SeasonStanding {
+    pub(crate) position: i64,
+    pub(crate) discord_id: i64,
+    pub(crate) exp: i64,
+    pub(crate) earned_role_id: Option<i64>,
+}
+
+#[derive(FromRow)]
+pub(crate) struct StreakBonus {
+    pub(crate) days: i64,
+    pub(crate) bonus_exp: i64,
+}
+
+#[derive(FromRow, Debug, Clone, Copy)]
+pub(crate) struct Streak {
+    /// 0 if the streak has been broken.
+    pub(crate) current_days: i64,
+    pub(crate) longest_days: i64,
+}
+
+/// Data Access Object for [`crate::app_state::event::ExpEvent`].
+#[derive(FromRow)]
+pub(crate) struct ExpEvent {
+    pub(crate) id: i64,
+    pub(crate) multiplier: f64,
+    pub(crate) starts_at: i64,
+    pub(crate) ends_at: i64,
+    pub(crate) channel_ids: Vec<i64>,
+    pub(crate) start_announced: bool,
 }
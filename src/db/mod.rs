@@ -1,27 +1,90 @@
-use crate::{app_state::exp::Exp, util::macros::i64_from_as_ref_user_id};
-use serenity::model::prelude::{RoleId, UserId};
+use crate::rank_card::theme::{Rgb, ThemeSetting};
+use crate::{
+    app_state::{
+        event::EventAnnouncement,
+        exp::{Exp, ExpAdjustment, ExpChange},
+        exp_rules::ExpSetting,
+        ladder::EarnedRoleMode,
+        level_up::LevelUpSetting,
+        roles::Presentation,
+        season::SeasonSetting,
+    },
+    util::macros::i64_from_as_ref_user_id,
+};
+use serenity::model::prelude::{ChannelId, MessageId, ReactionType, RoleId, UserId};
 use sqlx::PgPool;
 
 pub(crate) mod dao;
 
+/// Adds the signed amounts of exp to the user and records every change in the exp ledger.
+/// The adjustments made by moderators are also recorded in the audit trail,
+/// in the same transaction.
+///
+/// Exp never goes negative, so the recorded deltas can be smaller than the requested ones.
 pub(crate) async fn add_signed_exp(
     pool: &PgPool,
     discord_id: impl AsRef<UserId>,
-    delta: i64,
+    changes: &[ExpChange],
+    adjustment: Option<ExpAdjustment<'_>>,
 ) -> Result<Exp, sqlx::Error> {
     let discord_id: i64 = i64_from_as_ref_user_id!(discord_id);
-    sqlx::query_scalar(
-        "INSERT INTO app_users (discord_id, exp) \
-    VALUES ($1, $2) \
-    ON CONFLICT (discord_id) \
-    DO UPDATE SET exp = app_users.exp + $2 \
-    RETURNING exp",
-    )
-    .bind(discord_id)
-    .bind(delta)
-    .fetch_one(pool)
-    .await
-    .map(Exp::from_i64)
+
+    let mut tx = pool.begin().await?;
+    let old_exp: i64 =
+        sqlx::query_scalar("SELECT exp FROM app_users WHERE discord_id = $1 FOR UPDATE")
+            .bind(discord_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .unwrap_or(0);
+    let mut new_exp: i64 = old_exp;
+    for &ExpChange {
+        delta,
+        source,
+        channel_id,
+    } in changes
+    {
+        let prev_exp: i64 = new_exp;
+        new_exp = sqlx::query_scalar(
+            "INSERT INTO app_users (discord_id, exp) \
+        VALUES ($1, GREATEST($2, 0)) \
+        ON CONFLICT (discord_id) \
+        DO UPDATE SET exp = GREATEST(app_users.exp + $2, 0), \
+        last_active_at = CASE WHEN $3 THEN now() ELSE app_users.last_active_at END \
+        RETURNING exp",
+        )
+        .bind(discord_id)
+        .bind(delta)
+        .bind(source.is_activity())
+        .fetch_one(&mut *tx)
+        .await?;
+        let actual_delta: i64 = new_exp - prev_exp;
+        if actual_delta != 0 {
+            sqlx::query(
+                "INSERT INTO exp_ledger (discord_id, delta, source, channel_id) \
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(discord_id)
+            .bind(actual_delta)
+            .bind(source.as_str())
+            .bind(channel_id.map(i64::from))
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    if let Some(ExpAdjustment { actor_id, reason }) = adjustment {
+        sqlx::query(
+            "INSERT INTO exp_adjustments (actor_id, target_id, delta, reason) \
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(i64::from(actor_id))
+        .bind(discord_id)
+        .bind(new_exp - old_exp)
+        .bind(reason)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Exp::from_i64(new_exp))
 }
 
 /// Note that this function returns the active users based on the information
@@ -35,6 +98,34 @@ pub(crate) async fn server_members(pool: &PgPool) -> Result<Vec<dao::ServerMembe
     .await
 }
 
+/// Returns the members on the server whose exp is due to decay.
+///
+/// These are the members that have been inactive for `after_days` days,
+/// whose exp hasn't decayed in the last `period_days` days and is still above the floor.
+pub(crate) async fn decay_candidates(
+    pool: &PgPool,
+    after_days: u32,
+    period_days: u32,
+    floor: Exp,
+) -> Result<Vec<dao::ServerMember>, sqlx::Error> {
+    sqlx::query_as::<_, dao::ServerMember>(
+        "SELECT discord_id, exp FROM app_users \
+        WHERE on_server = true AND exp > $3 \
+        AND last_active_at <= now() - make_interval(days => $1) \
+        AND NOT EXISTS ( \
+            SELECT 1 FROM exp_ledger \
+            WHERE exp_ledger.discord_id = app_users.discord_id \
+            AND source = 'decay' \
+            AND recorded_at > now() - make_interval(days => $2) \
+        )",
+    )
+    .bind(i32::try_from(after_days).unwrap_or(i32::MAX))
+    .bind(i32::try_from(period_days).unwrap_or(i32::MAX))
+    .bind(floor.to_i64())
+    .fetch_all(pool)
+    .await
+}
+
 pub(crate) async fn mark_as_quitters(pool: &PgPool, quitters: &[i64]) -> Result<(), sqlx::Error> {
     if quitters.is_empty() {
         return Ok(());
@@ -94,6 +185,32 @@ pub(crate) async fn add_earned_role(
     Ok(())
 }
 
+pub(crate) async fn remove_earned_role(pool: &PgPool, role_id: RoleId) -> Result<(), sqlx::Error> {
+    let role_id = i64::from(role_id);
+    sqlx::query("DELETE FROM earned_roles WHERE role_id = $1")
+        .bind(role_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn earned_role_mode(pool: &PgPool) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT mode FROM earned_role_settings")
+        .fetch_one(pool)
+        .await
+}
+
+pub(crate) async fn set_earned_role_mode(
+    pool: &PgPool,
+    mode: EarnedRoleMode,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE earned_role_settings SET mode = $1")
+        .bind(mode.as_str())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub(crate) async fn sorted_earned_roles(
     pool: &PgPool,
 ) -> Result<Vec<dao::EarnedRole>, sqlx::Error> {
@@ -114,10 +231,800 @@ pub(crate) async fn sorted_self_assigned_roles(
         role_id, \
         message_id, \
         emoji_id, \
-        emoji_name 
+        emoji_name, \
+        presentation \
         FROM self_assigned_roles \
         ORDER BY message_id ASC",
     )
     .fetch_all(pool)
     .await
 }
+
+pub(crate) async fn add_self_assigned_role(
+    pool: &PgPool,
+    excl_role_group_id: i64,
+    role_id: RoleId,
+    message_id: MessageId,
+    emoji: &ReactionType,
+    presentation: Presentation,
+) -> Result<(), sqlx::Error> {
+    let role_id = i64::from(role_id);
+    let message_id = i64::from(message_id);
+    let (emoji_id, emoji_name): (Option<i64>, Option<&str>) = match emoji {
+        ReactionType::Custom { id, .. } => (Some(i64::from(*id)), None),
+        ReactionType::Unicode(name) => (None, Some(name.as_str())),
+        _ => unreachable!("Serenity doesn't produce other reaction types"),
+    };
+    sqlx::query(
+        "INSERT INTO self_assigned_roles \
+        (excl_role_group_id, role_id, message_id, emoji_id, emoji_name, presentation) \
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(excl_role_group_id)
+    .bind(role_id)
+    .bind(message_id)
+    .bind(emoji_id)
+    .bind(emoji_name)
+    .bind(presentation.as_str())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn remove_self_assigned_role(
+    pool: &PgPool,
+    role_id: RoleId,
+) -> Result<(), sqlx::Error> {
+    let role_id = i64::from(role_id);
+    sqlx::query("DELETE FROM self_assigned_roles WHERE role_id = $1")
+        .bind(role_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn remove_self_role_msg(
+    pool: &PgPool,
+    message_id: MessageId,
+) -> Result<(), sqlx::Error> {
+    let message_id = i64::from(message_id);
+    sqlx::query("DELETE FROM self_assigned_roles WHERE message_id = $1")
+        .bind(message_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn set_excl_role_group(
+    pool: &PgPool,
+    role_id: RoleId,
+    excl_role_group_id: i64,
+) -> Result<(), sqlx::Error> {
+    let role_id = i64::from(role_id);
+    sqlx::query(
+        "UPDATE self_assigned_roles \
+        SET excl_role_group_id = $2 \
+        WHERE role_id = $1",
+    )
+    .bind(role_id)
+    .bind(excl_role_group_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn set_self_role_msg_presentation(
+    pool: &PgPool,
+    message_id: MessageId,
+    presentation: Presentation,
+) -> Result<(), sqlx::Error> {
+    let message_id = i64::from(message_id);
+    sqlx::query(
+        "UPDATE self_assigned_roles \
+        SET presentation = $2 \
+        WHERE message_id = $1",
+    )
+    .bind(message_id)
+    .bind(presentation.as_str())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns an id of the group of mutually exclusive roles that is not used yet.
+pub(crate) async fn new_excl_role_group_id(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COALESCE(MAX(excl_role_group_id), 0) + 1 \
+        FROM self_assigned_roles",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub(crate) async fn exp_settings(pool: &PgPool) -> Result<dao::ExpSettings, sqlx::Error> {
+    sqlx::query_as::<_, dao::ExpSettings>(
+        "SELECT msg_cooldown_secs, min_msg_chars, ignore_duplicate_msgs, voice_exp_per_min, \
+        decay_after_days, decay_percent, decay_period_days, decay_floor \
+        FROM exp_settings",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub(crate) async fn set_exp_setting(pool: &PgPool, setting: ExpSetting) -> Result<(), sqlx::Error> {
+    let query = match setting {
+        ExpSetting::MsgCooldown(cooldown) => {
+            #[allow(clippy::cast_possible_wrap)]
+            let msg_cooldown_secs = cooldown.as_secs() as i64;
+            sqlx::query("UPDATE exp_settings SET msg_cooldown_secs = $1").bind(msg_cooldown_secs)
+        }
+        ExpSetting::MinMsgChars(min_msg_chars) => {
+            #[allow(clippy::cast_possible_wrap)]
+            let min_msg_chars = min_msg_chars as i64;
+            sqlx::query("UPDATE exp_settings SET min_msg_chars = $1").bind(min_msg_chars)
+        }
+        ExpSetting::IgnoreDuplicateMsgs(ignore) => {
+            sqlx::query("UPDATE exp_settings SET ignore_duplicate_msgs = $1").bind(ignore)
+        }
+        ExpSetting::VoiceExpPerMin(exp) => {
+            sqlx::query("UPDATE exp_settings SET voice_exp_per_min = $1").bind(exp)
+        }
+        ExpSetting::DecayAfterDays(days) => {
+            sqlx::query("UPDATE exp_settings SET decay_after_days = $1").bind(i64::from(days))
+        }
+        ExpSetting::DecayPercent(percent) => {
+            sqlx::query("UPDATE exp_settings SET decay_percent = $1").bind(i64::from(percent))
+        }
+        ExpSetting::DecayPeriodDays(days) => {
+            sqlx::query("UPDATE exp_settings SET decay_period_days = $1").bind(i64::from(days))
+        }
+        ExpSetting::DecayFloor(floor) => {
+            sqlx::query("UPDATE exp_settings SET decay_floor = $1").bind(Exp(floor).to_i64())
+        }
+    };
+    query.execute(pool).await?;
+    Ok(())
+}
+
+pub(crate) async fn channel_exp_multipliers(
+    pool: &PgPool,
+) -> Result<Vec<dao::ChannelExpMultiplier>, sqlx::Error> {
+    sqlx::query_as::<_, dao::ChannelExpMultiplier>(
+        "SELECT channel_id, multiplier FROM channel_exp_multipliers",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn role_exp_multipliers(
+    pool: &PgPool,
+) -> Result<Vec<dao::RoleExpMultiplier>, sqlx::Error> {
+    sqlx::query_as::<_, dao::RoleExpMultiplier>(
+        "SELECT role_id, multiplier FROM role_exp_multipliers",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn set_channel_exp_multiplier(
+    pool: &PgPool,
+    channel_id: ChannelId,
+    multiplier: f64,
+) -> Result<(), sqlx::Error> {
+    let channel_id = i64::from(channel_id);
+    sqlx::query(
+        "INSERT INTO channel_exp_multipliers (channel_id, multiplier) \
+        VALUES ($1, $2) \
+        ON CONFLICT (channel_id) \
+        DO UPDATE SET multiplier = $2",
+    )
+    .bind(channel_id)
+    .bind(multiplier)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn remove_channel_exp_multiplier(
+    pool: &PgPool,
+    channel_id: ChannelId,
+) -> Result<(), sqlx::Error> {
+    let channel_id = i64::from(channel_id);
+    sqlx::query("DELETE FROM channel_exp_multipliers WHERE channel_id = $1")
+        .bind(channel_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn set_role_exp_multiplier(
+    pool: &PgPool,
+    role_id: RoleId,
+    multiplier: f64,
+) -> Result<(), sqlx::Error> {
+    let role_id = i64::from(role_id);
+    sqlx::query(
+        "INSERT INTO role_exp_multipliers (role_id, multiplier) \
+        VALUES ($1, $2) \
+        ON CONFLICT (role_id) \
+        DO UPDATE SET multiplier = $2",
+    )
+    .bind(role_id)
+    .bind(multiplier)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn remove_role_exp_multiplier(
+    pool: &PgPool,
+    role_id: RoleId,
+) -> Result<(), sqlx::Error> {
+    let role_id = i64::from(role_id);
+    sqlx::query("DELETE FROM role_exp_multipliers WHERE role_id = $1")
+        .bind(role_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns a page of the members who are on the server, ordered by exp from the highest.
+pub(crate) async fn leaderboard_page(
+    pool: &PgPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<dao::ServerMember>, sqlx::Error> {
+    sqlx::query_as::<_, dao::ServerMember>(
+        "SELECT discord_id, exp FROM app_users \
+        WHERE on_server = true \
+        ORDER BY exp DESC, discord_id ASC \
+        LIMIT $1 OFFSET $2",
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn count_server_members(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM app_users WHERE on_server = true")
+        .fetch_one(pool)
+        .await
+}
+
+/// Returns the 1-based position of the member on the leaderboard along with their exp,
+/// if the member is on the server.
+pub(crate) async fn leaderboard_position(
+    pool: &PgPool,
+    discord_id: impl AsRef<UserId>,
+) -> Result<Option<(i64, Exp)>, sqlx::Error> {
+    let discord_id: i64 = i64_from_as_ref_user_id!(discord_id);
+    let row: Option<(i64, i64)> = sqlx::query_as(
+        "SELECT \
+            (SELECT COUNT(*) + 1 FROM app_users AS other \
+            WHERE other.on_server = true \
+            AND (other.exp > me.exp \
+            OR (other.exp = me.exp AND other.discord_id < me.discord_id))), \
+            me.exp \
+        FROM app_users AS me \
+        WHERE me.discord_id = $1 AND me.on_server = true",
+    )
+    .bind(discord_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(position, exp)| (position, Exp::from_i64(exp))))
+}
+
+pub(crate) async fn rank_card_theme(pool: &PgPool) -> Result<dao::RankCardTheme, sqlx::Error> {
+    sqlx::query_as::<_, dao::RankCardTheme>(
+        "SELECT layout, background, text, bar, accent FROM rank_card_theme",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub(crate) async fn set_theme_setting(
+    pool: &PgPool,
+    setting: ThemeSetting,
+) -> Result<(), sqlx::Error> {
+    #[allow(clippy::cast_possible_wrap)]
+    let colour = |rgb: Rgb| rgb.to_u32() as i32;
+    let query = match setting {
+        ThemeSetting::Layout(layout) => {
+            sqlx::query("UPDATE rank_card_theme SET layout = $1").bind(layout.as_str())
+        }
+        ThemeSetting::Background(rgb) => {
+            sqlx::query("UPDATE rank_card_theme SET background = $1").bind(colour(rgb))
+        }
+        ThemeSetting::Text(rgb) => {
+            sqlx::query("UPDATE rank_card_theme SET text = $1").bind(colour(rgb))
+        }
+        ThemeSetting::Bar(rgb) => {
+            sqlx::query("UPDATE rank_card_theme SET bar = $1").bind(colour(rgb))
+        }
+        ThemeSetting::Accent(rgb) => {
+            sqlx::query("UPDATE rank_card_theme SET accent = $1").bind(colour(rgb))
+        }
+    };
+    query.execute(pool).await?;
+    Ok(())
+}
+
+pub(crate) async fn level_up_settings(pool: &PgPool) -> Result<dao::LevelUpSettings, sqlx::Error> {
+    sqlx::query_as::<_, dao::LevelUpSettings>(
+        "SELECT enabled, channel_id, template FROM level_up_settings",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub(crate) async fn set_level_up_setting(
+    pool: &PgPool,
+    setting: &LevelUpSetting,
+) -> Result<(), sqlx::Error> {
+    let query = match setting {
+        LevelUpSetting::Enabled(enabled) => {
+            sqlx::query("UPDATE level_up_settings SET enabled = $1").bind(*enabled)
+        }
+        LevelUpSetting::Channel(channel_id) => {
+            let channel_id: Option<i64> = channel_id.map(i64::from);
+            sqlx::query("UPDATE level_up_settings SET channel_id = $1").bind(channel_id)
+        }
+        LevelUpSetting::Template(template) => {
+            sqlx::query("UPDATE level_up_settings SET template = $1").bind(template.as_str())
+        }
+    };
+    query.execute(pool).await?;
+    Ok(())
+}
+
+pub(crate) async fn level_up_ping_opt_outs(pool: &PgPool) -> Result<Vec<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT discord_id FROM level_up_ping_opt_outs")
+        .fetch_all(pool)
+        .await
+}
+
+pub(crate) async fn set_level_up_pings(
+    pool: &PgPool,
+    discord_id: UserId,
+    pings: bool,
+) -> Result<(), sqlx::Error> {
+    let discord_id = i64::from(discord_id);
+    let query = if pings {
+        sqlx::query("DELETE FROM level_up_ping_opt_outs WHERE discord_id = $1")
+    } else {
+        sqlx::query(
+            "INSERT INTO level_up_ping_opt_outs (discord_id) \
+            VALUES ($1) \
+            ON CONFLICT (discord_id) DO NOTHING",
+        )
+    };
+    query.bind(discord_id).execute(pool).await?;
+    Ok(())
+}
+
+/// Returns the sums of the exp changes of the user in each of the last `periods` periods,
+/// oldest first. `period` is either `"day"` or `"week"`, and the periods start in UTC.
+///
+/// The periods are labeled by their first day as `YYYY-MM-DD`.
+pub(crate) async fn exp_history(
+    pool: &PgPool,
+    discord_id: UserId,
+    period: &str,
+    periods: i32,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let discord_id = i64::from(discord_id);
+    sqlx::query_as(
+        "SELECT to_char(period, 'YYYY-MM-DD'), COALESCE(SUM(exp_ledger.delta), 0)::bigint \
+        FROM generate_series( \
+            date_trunc($2, now() AT TIME ZONE 'UTC') - ($3 - 1) * ('1 ' || $2)::interval, \
+            date_trunc($2, now() AT TIME ZONE 'UTC'), \
+            ('1 ' || $2)::interval \
+        ) AS period \
+        LEFT JOIN exp_ledger \
+            ON exp_ledger.discord_id = $1 \
+            AND date_trunc($2, exp_ledger.recorded_at AT TIME ZONE 'UTC') = period \
+        GROUP BY period \
+        ORDER BY period",
+    )
+    .bind(discord_id)
+    .bind(period)
+    .bind(periods)
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns the sums of the exp changes of the user in the last `periods` periods
+/// for each source that changed the exp.
+///
+/// The periods are the same as the ones of [`exp_history`], so the sums add up to its total.
+pub(crate) async fn exp_history_by_source(
+    pool: &PgPool,
+    discord_id: UserId,
+    period: &str,
+    periods: i32,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let discord_id = i64::from(discord_id);
+    sqlx::query_as(
+        "SELECT source, SUM(delta)::bigint \
+        FROM exp_ledger \
+        WHERE discord_id = $1 \
+        AND recorded_at AT TIME ZONE 'UTC' \
+            >= date_trunc($2, now() AT TIME ZONE 'UTC') - ($3 - 1) * ('1 ' || $2)::interval \
+        GROUP BY source \
+        ORDER BY source",
+    )
+    .bind(discord_id)
+    .bind(period)
+    .bind(periods)
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn season_settings(pool: &PgPool) -> Result<dao::SeasonSettings, sqlx::Error> {
+    sqlx::query_as::<_, dao::SeasonSettings>(
+        "SELECT length_days, kept_percent FROM season_settings",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub(crate) async fn set_season_setting(
+    pool: &PgPool,
+    setting: SeasonSetting,
+) -> Result<(), sqlx::Error> {
+    let query = match setting {
+        SeasonSetting::LengthDays(days) => {
+            sqlx::query("UPDATE season_settings SET length_days = $1").bind(i64::from(days))
+        }
+        SeasonSetting::KeptPercent(percent) => {
+            sqlx::query("UPDATE season_settings SET kept_percent = $1").bind(i64::from(percent))
+        }
+    };
+    query.execute(pool).await?;
+    Ok(())
+}
+
+/// Returns whether the current season has lasted as long as the seasons should.
+pub(crate) async fn season_due(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT length_days > 0 \
+        AND started_at <= now() - make_interval(days => length_days::int) \
+        FROM season_settings",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Archives the standings of the members on the server, keeps `kept_percent` percent
+/// of the exp of every user and starts a new season.
+///
+/// Returns the number of the archived season.
+pub(crate) async fn end_season(pool: &PgPool, kept_percent: u32) -> Result<i64, sqlx::Error> {
+    let kept_percent = i64::from(kept_percent);
+    let mut tx = pool.begin().await?;
+    // Locking the settings keeps two seasons from ending at the same time
+    sqlx::query("SELECT started_at FROM season_settings FOR UPDATE")
+        .execute(&mut *tx)
+        .await?;
+    let number: i64 = sqlx::query_scalar(
+        "INSERT INTO seasons (number, started_at, kept_percent) \
+        SELECT (SELECT COALESCE(MAX(number), 0) + 1 FROM seasons), started_at, $1 \
+        FROM season_settings \
+        RETURNING number",
+    )
+    .bind(kept_percent)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO season_standings (season, discord_id, position, exp, earned_role_id) \
+        SELECT $1, discord_id, ROW_NUMBER() OVER (ORDER BY exp DESC, discord_id ASC), exp, \
+            (SELECT role_id FROM earned_roles \
+            WHERE exp_needed <= app_users.exp \
+            ORDER BY exp_needed DESC LIMIT 1) \
+        FROM app_users \
+        WHERE on_server = true",
+    )
+    .bind(number)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO exp_ledger (discord_id, delta, source) \
+        SELECT discord_id, div(exp::numeric * $1, 100)::bigint - exp, 'season' FROM app_users \
+        WHERE div(exp::numeric * $1, 100) <> exp",
+    )
+    .bind(kept_percent)
+    .execute(&mut *tx)
+    .await?;
+    // Widened to numeric so that the product can't overflow
+    sqlx::query(
+        "UPDATE app_users SET exp = div(exp::numeric * $1, 100)::bigint \
+        WHERE div(exp::numeric * $1, 100) <> exp",
+    )
+    .bind(kept_percent)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE season_settings SET started_at = now()")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(number)
+}
+
+/// Returns the archived seasons, the latest first.
+pub(crate) async fn seasons(pool: &PgPool) -> Result<Vec<dao::Season>, sqlx::Error> {
+    sqlx::query_as::<_, dao::Season>(
+        "SELECT number, \
+            to_char(started_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS started_on, \
+            to_char(ended_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS ended_on, \
+            kept_percent, \
+            (SELECT COUNT(*) FROM season_standings WHERE season = number) AS member_count \
+        FROM seasons \
+        ORDER BY number DESC",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn season(pool: &PgPool, number: i64) -> Result<Option<dao::Season>, sqlx::Error> {
+    sqlx::query_as::<_, dao::Season>(
+        "SELECT number, \
+            to_char(started_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS started_on, \
+            to_char(ended_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS ended_on, \
+            kept_percent, \
+            (SELECT COUNT(*) FROM season_standings WHERE season = number) AS member_count \
+        FROM seasons \
+        WHERE number = $1",
+    )
+    .bind(number)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns a page of the final standings of the season, ordered by position.
+pub(crate) async fn season_standings_page(
+    pool: &PgPool,
+    season: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<dao::SeasonStanding>, sqlx::Error> {
+    sqlx::query_as::<_, dao::SeasonStanding>(
+        "SELECT position, discord_id, exp, earned_role_id FROM season_standings \
+        WHERE season = $1 \
+        ORDER BY position ASC \
+        LIMIT $2 OFFSET $3",
+    )
+    .bind(season)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns all final standings of the season, ordered by position.
+pub(crate) async fn season_standings(
+    pool: &PgPool,
+    season: i64,
+) -> Result<Vec<dao::SeasonStanding>, sqlx::Error> {
+    sqlx::query_as::<_, dao::SeasonStanding>(
+        "SELECT position, discord_id, exp, earned_role_id FROM season_standings \
+        WHERE season = $1 \
+        ORDER BY position ASC",
+    )
+    .bind(season)
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn season_standing(
+    pool: &PgPool,
+    season: i64,
+    discord_id: impl AsRef<UserId>,
+) -> Result<Option<dao::SeasonStanding>, sqlx::Error> {
+    let discord_id: i64 = i64_from_as_ref_user_id!(discord_id);
+    sqlx::query_as::<_, dao::SeasonStanding>(
+        "SELECT position, discord_id, exp, earned_role_id FROM season_standings \
+        WHERE season = $1 AND discord_id = $2",
+    )
+    .bind(season)
+    .bind(discord_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub(crate) async fn streak_timezone(pool: &PgPool) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar("SELECT timezone FROM streak_settings")
+        .fetch_one(pool)
+        .await
+}
+
+pub(crate) async fn set_streak_timezone(pool: &PgPool, timezone: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE streak_settings SET timezone = $1")
+        .bind(timezone)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns whether the database knows the timezone with the IANA name.
+pub(crate) async fn is_timezone(pool: &PgPool, name: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+        .bind(name)
+        .fetch_one(pool)
+        .await
+}
+
+pub(crate) async fn streak_bonuses(pool: &PgPool) -> Result<Vec<dao::StreakBonus>, sqlx::Error> {
+    sqlx::query_as::<_, dao::StreakBonus>("SELECT days, bonus_exp FROM streak_bonuses")
+        .fetch_all(pool)
+        .await
+}
+
+/// Sets the bonus exp for reaching a streak of `days` days. `None` removes the bonus.
+pub(crate) async fn set_streak_bonus(
+    pool: &PgPool,
+    days: u32,
+    bonus: Option<Exp>,
+) -> Result<(), sqlx::Error> {
+    let days = i64::from(days);
+    match bonus {
+        Some(bonus) => {
+            sqlx::query(
+                "INSERT INTO streak_bonuses (days, bonus_exp) VALUES ($1, $2) \
+                ON CONFLICT (days) DO UPDATE SET bonus_exp = $2",
+            )
+            .bind(days)
+            .bind(bonus.to_i64())
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM streak_bonuses WHERE days = $1")
+                .bind(days)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the current day in the timezone to the streak of the user.
+///
+/// The streak goes on if the previous day was the last day of the streak
+/// and starts anew otherwise. Returns the length of the streak, or `None`
+/// if the current day has already been added.
+pub(crate) async fn record_streak_day(
+    pool: &PgPool,
+    discord_id: impl AsRef<UserId>,
+    timezone: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let discord_id: i64 = i64_from_as_ref_user_id!(discord_id);
+    sqlx::query_scalar(
+        "INSERT INTO activity_streaks (discord_id, current_days, longest_days, last_day) \
+        VALUES ($1, 1, 1, (now() AT TIME ZONE $2)::date) \
+        ON CONFLICT (discord_id) DO UPDATE SET \
+        current_days = CASE WHEN activity_streaks.last_day = EXCLUDED.last_day - 1 \
+            THEN activity_streaks.current_days + 1 ELSE 1 END, \
+        longest_days = GREATEST(activity_streaks.longest_days, \
+            CASE WHEN activity_streaks.last_day = EXCLUDED.last_day - 1 \
+            THEN activity_streaks.current_days + 1 ELSE 1 END), \
+        last_day = EXCLUDED.last_day \
+        WHERE activity_streaks.last_day < EXCLUDED.last_day \
+        RETURNING current_days",
+    )
+    .bind(discord_id)
+    .bind(timezone)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns the current and the longest streak of the user. The current streak
+/// is 0 if the user has missed a day in the timezone.
+pub(crate) async fn streak(
+    pool: &PgPool,
+    discord_id: impl AsRef<UserId>,
+    timezone: &str,
+) -> Result<Option<dao::Streak>, sqlx::Error> {
+    let discord_id: i64 = i64_from_as_ref_user_id!(discord_id);
+    sqlx::query_as::<_, dao::Streak>(
+        "SELECT \
+            CASE WHEN last_day >= (now() AT TIME ZONE $2)::date - 1 \
+            THEN current_days ELSE 0 END AS current_days, \
+            longest_days \
+        FROM activity_streaks \
+        WHERE discord_id = $1",
+    )
+    .bind(discord_id)
+    .bind(timezone)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Converts the local time in the timezone to the number of seconds since the Unix epoch.
+///
+/// Returns `None` if the database can't make sense of the time.
+pub(crate) async fn parse_local_time(
+    pool: &PgPool,
+    local_time: &str,
+    timezone: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let res: Result<i64, sqlx::Error> =
+        sqlx::query_scalar("SELECT extract(epoch FROM ($1::timestamp AT TIME ZONE $2))::bigint")
+            .bind(local_time)
+            .bind(timezone)
+            .fetch_one(pool)
+            .await;
+    match res {
+        Ok(unix_time) => Ok(Some(unix_time)),
+        // The class 22 stands for the invalid data, such as a malformed time
+        Err(sqlx::Error::Database(e)) if e.code().is_some_and(|code| code.starts_with("22")) => {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the exp events that haven't ended yet along with the ended ones
+/// whose start was announced but whose end wasn't.
+pub(crate) async fn exp_events(pool: &PgPool) -> Result<Vec<dao::ExpEvent>, sqlx::Error> {
+    sqlx::query_as::<_, dao::ExpEvent>(
+        "SELECT id, multiplier, \
+            extract(epoch FROM starts_at)::bigint AS starts_at, \
+            extract(epoch FROM ends_at)::bigint AS ends_at, \
+            channel_ids, start_announced \
+        FROM exp_events \
+        WHERE NOT end_announced \
+        AND (ends_at > now() OR start_announced)",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Schedules an exp event and returns its id.
+pub(crate) async fn add_exp_event(
+    pool: &PgPool,
+    created_by: impl AsRef<UserId>,
+    multiplier: f64,
+    starts_at: i64,
+    ends_at: i64,
+    channel_ids: &[ChannelId],
+) -> Result<i64, sqlx::Error> {
+    let created_by: i64 = i64_from_as_ref_user_id!(created_by);
+    let channel_ids: Vec<i64> = channel_ids.iter().copied().map(i64::from).collect();
+    sqlx::query_scalar(
+        "INSERT INTO exp_events (multiplier, starts_at, ends_at, channel_ids, created_by) \
+        VALUES ($1, to_timestamp($2), to_timestamp($3), $4, $5) \
+        RETURNING id",
+    )
+    .bind(multiplier)
+    .bind(starts_at)
+    .bind(ends_at)
+    .bind(channel_ids)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+}
+
+pub(crate) async fn remove_exp_event(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM exp_events WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn mark_exp_event_announced(
+    pool: &PgPool,
+    announcement: &EventAnnouncement,
+) -> Result<(), sqlx::Error> {
+    let query = match announcement {
+        EventAnnouncement::Started(event) => {
+            sqlx::query("UPDATE exp_events SET start_announced = true WHERE id = $1").bind(event.id)
+        }
+        EventAnnouncement::Ended(event) => {
+            sqlx::query("UPDATE exp_events SET end_announced = true WHERE id = $1").bind(event.id)
+        }
+    };
+    query.execute(pool).await?;
+    Ok(())
+}
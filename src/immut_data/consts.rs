@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serenity::prelude::GatewayIntents;
 
 pub(crate) const SCHEMA: &str = include_str!("../../schema.pgsql");
@@ -5,10 +7,47 @@ pub(crate) const SCHEMA: &str = include_str!("../../schema.pgsql");
 pub(crate) const DISCORD_INTENTS: GatewayIntents = {
     let fst = GatewayIntents::GUILD_MESSAGES.bits();
     let snd = GatewayIntents::MESSAGE_CONTENT.bits();
-    match GatewayIntents::from_bits(fst | snd) {
+    let trd = GatewayIntents::GUILD_MESSAGE_REACTIONS.bits();
+    let fth = GatewayIntents::GUILD_VOICE_STATES.bits();
+    // Fills the cache with the guild, whose voice states are rewarded with exp
+    let fif = GatewayIntents::GUILDS.bits();
+    match GatewayIntents::from_bits(fst | snd | trd | fth | fif) {
         Some(intents) => intents,
         None => panic!("Invalid intents"),
     }
 };
 
 pub(crate) const EXP_PER_MSG: i64 = 5;
+
+/// The highest multiplier of the experience points, for a single channel or role
+/// as well as for all of them combined.
+pub(crate) const MAX_EXP_MULTIPLIER: f64 = 10.0;
+
+/// How often the time spent in voice channels is converted into experience points.
+pub(crate) const VOICE_EXP_TICK: Duration = Duration::from_secs(60);
+
+/// How often the exp of the inactive members is checked for decay.
+pub(crate) const DECAY_TICK: Duration = Duration::from_secs(60 * 60);
+
+/// How often the current season is checked for having lasted long enough.
+pub(crate) const SEASON_TICK: Duration = Duration::from_secs(60 * 60);
+
+/// How often the exp events are checked for having started or ended.
+pub(crate) const EXP_EVENT_TICK: Duration = Duration::from_secs(60);
+
+/// The prefix of the custom ids of the message components attached to self-role messages.
+///
+/// Buttons append the id of the role they toggle. Dropdowns append [`SELF_ROLE_CHOICE_SUFFIX`].
+pub(crate) const SELF_ROLE_CUSTOM_ID_PREFIX: &str = "selfrole:";
+
+/// The suffix of the custom id of the dropdown attached to a self-role message of a choice group.
+pub(crate) const SELF_ROLE_CHOICE_SUFFIX: &str = "choice";
+
+/// The prefix of the custom ids of the buttons that turn the pages of the leaderboard.
+///
+/// The buttons append the page number. The buttons of the leaderboards of the archived seasons
+/// also append the number of the season, separated by `:`.
+pub(crate) const LEADERBOARD_CUSTOM_ID_PREFIX: &str = "leaderboard:";
+
+/// The number of members on a page of the leaderboard.
+pub(crate) const LEADERBOARD_PAGE_SIZE: i64 = 10;
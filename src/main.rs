@@ -6,6 +6,7 @@ mod bots;
 mod commands;
 mod db;
 pub(crate) mod immut_data;
+mod rank_card;
 pub(crate) mod util;
 use bots::MainBot;
 use util::build_client;
@@ -0,0 +1,88 @@
+//! Rendering of the bar charts of the exp history of a member.
+
+use super::{fit_text, text_width, theme::Theme, Canvas};
+
+/// The data shown on the exp history chart.
+#[derive(Debug)]
+pub(crate) struct ExpChart {
+    pub(crate) title: String,
+    /// The labels of the periods along with the exp change in them, oldest first.
+    pub(crate) bars: Vec<(String, i64)>,
+}
+
+/// Renders the chart as a PNG image.
+///
+/// Gains are drawn above the baseline in the accent color and losses below it
+/// in the color of the progress bar track.
+pub(crate) fn render(chart: &ExpChart, theme: &Theme) -> std::io::Result<Vec<u8>> {
+    const WIDTH: u32 = 640;
+    const HEIGHT: u32 = 320;
+    const PLOT_X: i64 = 20;
+    const PLOT_Y: i64 = 70;
+    const PLOT_WIDTH: i64 = 600;
+    const PLOT_HEIGHT: i64 = 200;
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, theme.background);
+    let title = fit_text(&chart.title, 3, PLOT_WIDTH);
+    canvas.draw_text(PLOT_X, 20, 3, theme.text, &title);
+
+    // The amounts of exp are widened so that the extreme values can't overflow
+    let max_gain: i128 = chart
+        .bars
+        .iter()
+        .map(|(_, v)| i128::from(*v))
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    let max_loss: i128 = chart
+        .bars
+        .iter()
+        .map(|(_, v)| -i128::from(*v))
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    let range: i128 = (max_gain + max_loss).max(1);
+    // Never exceeds `PLOT_HEIGHT` since the amount is at most the range
+    let scale = |amount: i128| -> i64 {
+        i64::try_from(i128::from(PLOT_HEIGHT) * amount / range).unwrap_or(PLOT_HEIGHT)
+    };
+    let baseline_y: i64 = PLOT_Y + scale(max_gain);
+
+    let slots = i64::try_from(chart.bars.len()).unwrap_or(i64::MAX).max(1);
+    let slot_width: i64 = PLOT_WIDTH / slots;
+    let bar_width: i64 = (slot_width * 7 / 10).max(1);
+    for (i, (label, value)) in (0..).zip(&chart.bars) {
+        let slot_x: i64 = PLOT_X + i * slot_width;
+        let bar_x: i64 = slot_x + (slot_width - bar_width) / 2;
+        let bar_height: i64 = scale(i128::from(*value).abs());
+        let center_x: i64 = slot_x + slot_width / 2;
+
+        if *value >= 0 {
+            canvas.fill_rect(
+                bar_x,
+                baseline_y - bar_height,
+                bar_width,
+                bar_height,
+                theme.accent,
+            );
+        } else {
+            canvas.fill_rect(bar_x, baseline_y, bar_width, bar_height, theme.bar);
+        }
+
+        let value_text = value.to_string();
+        if *value != 0 && text_width(&value_text, 1) <= slot_width {
+            let value_y = if *value > 0 {
+                baseline_y - bar_height - 10
+            } else {
+                baseline_y + bar_height + 3
+            };
+            canvas.draw_centered_text(center_x, value_y, 1, theme.text, &value_text);
+        }
+        if text_width(label, 1) <= slot_width {
+            canvas.draw_centered_text(center_x, PLOT_Y + PLOT_HEIGHT + 20, 1, theme.text, label);
+        }
+    }
+    canvas.fill_rect(PLOT_X, baseline_y, PLOT_WIDTH, 1, theme.text);
+
+    canvas.into_png()
+}
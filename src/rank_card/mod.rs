@@ -0,0 +1,260 @@
+//! Rendering of rank cards, the PNG images that show the rank of a member.
+//!
+//! Everything is drawn in-process with a built-in bitmap font, so no fonts
+//! or external services are needed.
+
+pub(crate) mod chart;
+mod font;
+mod png;
+pub(crate) mod theme;
+
+use theme::{Layout, Rgb, Theme};
+
+/// The data shown on the rank card of a member.
+#[derive(Debug)]
+pub(crate) struct RankCard {
+    pub(crate) display_name: String,
+    pub(crate) exp: u64,
+    /// The exp needed for the next earned role, if there is one.
+    pub(crate) next_exp: Option<u64>,
+    /// The progress towards the next earned role, between 0 and 1.
+    pub(crate) progress: f64,
+    pub(crate) earned_role_name: Option<String>,
+    /// The color of the earned role, if the role has one.
+    pub(crate) earned_role_colour: Option<Rgb>,
+}
+
+/// An RGBA image that can be drawn on.
+struct Canvas {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, background: Rgb) -> Self {
+        let Rgb(r, g, b) = background;
+        let rgba = [r, g, b, 0xFF].repeat(width as usize * height as usize);
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// Blends the color into the pixel. `coverage` is the opacity between 0 and 1.
+    fn blend(&mut self, x: i64, y: i64, colour: Rgb, coverage: f64) {
+        if x < 0 || y < 0 || x >= i64::from(self.width) || y >= i64::from(self.height) {
+            return;
+        }
+        if coverage <= 0.0 {
+            return;
+        }
+        let coverage = coverage.min(1.0);
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let idx = (y as usize * self.width as usize + x as usize) * 4;
+        let Rgb(r, g, b) = colour;
+        for (channel, value) in self.rgba[idx..idx + 3].iter_mut().zip([r, g, b]) {
+            let blended = f64::from(*channel) * (1.0 - coverage) + f64::from(value) * coverage;
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+            let blended = blended.round() as u8;
+            *channel = blended;
+        }
+    }
+
+    fn fill_rect(&mut self, x: i64, y: i64, w: i64, h: i64, colour: Rgb) {
+        for py in y..y + h {
+            for px in x..x + w {
+                self.blend(px, py, colour, 1.0);
+            }
+        }
+    }
+
+    /// Fills the rectangle with rounded corners. The edges are anti-aliased.
+    fn fill_rounded_rect(&mut self, x: i64, y: i64, w: i64, h: i64, radius: f64, colour: Rgb) {
+        #[allow(clippy::cast_precision_loss)]
+        let (left, top, right, bottom) = (x as f64, y as f64, (x + w) as f64, (y + h) as f64);
+        for py in y..y + h {
+            for px in x..x + w {
+                #[allow(clippy::cast_precision_loss)]
+                let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                // distance from the rectangle shrunk by the radius
+                let dx = (left + radius - cx).max(cx - (right - radius)).max(0.0);
+                let dy = (top + radius - cy).max(cy - (bottom - radius)).max(0.0);
+                let distance = dx.hypot(dy);
+                self.blend(px, py, colour, radius + 0.5 - distance);
+            }
+        }
+    }
+
+    /// Fills the circle. The edge is anti-aliased.
+    fn fill_circle(&mut self, cx: i64, cy: i64, radius: i64, colour: Rgb) {
+        #[allow(clippy::cast_precision_loss)]
+        let r = radius as f64;
+        for py in cy - radius - 1..=cy + radius + 1 {
+            for px in cx - radius - 1..=cx + radius + 1 {
+                #[allow(clippy::cast_precision_loss)]
+                let distance = ((px - cx) as f64 + 0.5).hypot((py - cy) as f64 + 0.5);
+                self.blend(px, py, colour, r + 0.5 - distance);
+            }
+        }
+    }
+
+    /// Draws the text with its top left corner at the given point.
+    /// Every font pixel becomes a `scale`x`scale` square.
+    fn draw_text(&mut self, x: i64, y: i64, scale: i64, colour: Rgb, text: &str) {
+        for (i, c) in (0..).zip(text.chars()) {
+            let glyph_x = x + i * i64::from(font::ADVANCE) * scale;
+            for (col, bits) in (0..).zip(font::glyph(c)) {
+                for row in 0..i64::from(font::GLYPH_HEIGHT) {
+                    if bits >> row & 1 == 1 {
+                        self.fill_rect(
+                            glyph_x + col * scale,
+                            y + row * scale,
+                            scale,
+                            scale,
+                            colour,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_centered_text(&mut self, cx: i64, y: i64, scale: i64, colour: Rgb, text: &str) {
+        let x = cx - text_width(text, scale) / 2;
+        self.draw_text(x, y, scale, colour, text);
+    }
+
+    fn into_png(self) -> std::io::Result<Vec<u8>> {
+        png::encode(self.width, self.height, &self.rgba)
+    }
+}
+
+fn text_width(text: &str, scale: i64) -> i64 {
+    let chars = i64::try_from(text.chars().count()).unwrap_or(i64::MAX / 64);
+    if chars == 0 {
+        return 0;
+    }
+    (chars * i64::from(font::ADVANCE) - 1) * scale
+}
+
+/// Shortens the text with an ellipsis so that it fits into the width.
+fn fit_text(text: &str, scale: i64, max_width: i64) -> String {
+    if text_width(text, scale) <= max_width {
+        return text.to_string();
+    }
+    let max_chars = (max_width / scale + 1) / i64::from(font::ADVANCE);
+    let kept = usize::try_from(max_chars - 3).unwrap_or_default();
+    let mut fitted: String = text.chars().take(kept).collect();
+    fitted.push_str("...");
+    fitted
+}
+
+/// Returns the initials of the name, or `"?"` if the name has no suitable characters.
+fn initials(name: &str) -> String {
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(char::is_ascii_alphanumeric))
+        .take(2)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if initials.is_empty() {
+        "?".to_string()
+    } else {
+        initials
+    }
+}
+
+fn exp_line(card: &RankCard) -> String {
+    match card.next_exp {
+        Some(next_exp) => format!("{} / {next_exp} EXP", card.exp),
+        None => format!("{} EXP", card.exp),
+    }
+}
+
+fn draw_avatar(canvas: &mut Canvas, theme: &Theme, cx: i64, cy: i64, radius: i64, name: &str) {
+    const INITIALS_SCALE: i64 = 6;
+    canvas.fill_circle(cx, cy, radius, theme.accent);
+    let y = cy - i64::from(font::GLYPH_HEIGHT) * INITIALS_SCALE / 2;
+    canvas.draw_centered_text(cx, y, INITIALS_SCALE, theme.text, &initials(name));
+}
+
+fn draw_progress_bar(
+    canvas: &mut Canvas,
+    theme: &Theme,
+    card: &RankCard,
+    (x, y, w, h): (i64, i64, i64, i64),
+) {
+    #[allow(clippy::cast_precision_loss)]
+    let radius = h as f64 / 2.0;
+    canvas.fill_rounded_rect(x, y, w, h, radius, theme.bar);
+    if card.progress > 0.0 {
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        let filled = ((w as f64 * card.progress.min(1.0)).round() as i64).max(h);
+        let fill_colour = card.earned_role_colour.unwrap_or(theme.accent);
+        canvas.fill_rounded_rect(x, y, filled, h, radius, fill_colour);
+    }
+}
+
+fn render_wide(card: &RankCard, theme: &Theme) -> Canvas {
+    const WIDTH: u32 = 640;
+    const HEIGHT: u32 = 180;
+    const TEXT_X: i64 = 180;
+    const TEXT_WIDTH: i64 = 430;
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, theme.background);
+    draw_avatar(&mut canvas, theme, 90, 90, 62, &card.display_name);
+
+    let name = fit_text(&card.display_name, 4, TEXT_WIDTH);
+    canvas.draw_text(TEXT_X, 30, 4, theme.text, &name);
+
+    let exp_line = exp_line(card);
+    let exp_width = text_width(&exp_line, 2);
+    canvas.draw_text(
+        TEXT_X + TEXT_WIDTH - exp_width,
+        80,
+        2,
+        theme.text,
+        &exp_line,
+    );
+    if let Some(role_name) = &card.earned_role_name {
+        let role_name = fit_text(role_name, 2, TEXT_WIDTH - exp_width - 20);
+        let role_colour = card.earned_role_colour.unwrap_or(theme.text);
+        canvas.draw_text(TEXT_X, 80, 2, role_colour, &role_name);
+    }
+
+    draw_progress_bar(&mut canvas, theme, card, (TEXT_X, 112, TEXT_WIDTH, 32));
+    canvas
+}
+
+fn render_stacked(card: &RankCard, theme: &Theme) -> Canvas {
+    const WIDTH: u32 = 360;
+    const HEIGHT: u32 = 300;
+    const CENTER_X: i64 = 180;
+    const TEXT_WIDTH: i64 = 320;
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, theme.background);
+    draw_avatar(&mut canvas, theme, CENTER_X, 80, 56, &card.display_name);
+
+    let name = fit_text(&card.display_name, 3, TEXT_WIDTH);
+    canvas.draw_centered_text(CENTER_X, 152, 3, theme.text, &name);
+    if let Some(role_name) = &card.earned_role_name {
+        let role_name = fit_text(role_name, 2, TEXT_WIDTH);
+        let role_colour = card.earned_role_colour.unwrap_or(theme.text);
+        canvas.draw_centered_text(CENTER_X, 188, 2, role_colour, &role_name);
+    }
+    canvas.draw_centered_text(CENTER_X, 212, 2, theme.text, &exp_line(card));
+
+    draw_progress_bar(&mut canvas, theme, card, (20, 246, TEXT_WIDTH, 26));
+    canvas
+}
+
+/// Renders the rank card as a PNG image.
+pub(crate) fn render(card: &RankCard, theme: &Theme) -> std::io::Result<Vec<u8>> {
+    let canvas = match theme.layout {
+        Layout::Wide => render_wide(card, theme),
+        Layout::Stacked => render_stacked(card, theme),
+    };
+    canvas.into_png()
+}
@@ -0,0 +1,50 @@
+//! A minimal encoder of truecolor PNG images with alpha channel.
+
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression, Crc};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// 8 bits per channel
+const BIT_DEPTH: u8 = 8;
+/// RGBA
+const COLOR_TYPE: u8 = 6;
+/// Scanlines are stored as they are
+const FILTER_NONE: u8 = 0;
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let len = u32::try_from(data.len()).expect("PNG chunk is too large");
+    png.extend_from_slice(&len.to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// Encodes the RGBA pixels, stored row by row, as a PNG image.
+pub(super) fn encode(width: u32, height: u32, rgba: &[u8]) -> std::io::Result<Vec<u8>> {
+    let row_len = width as usize * 4;
+    debug_assert_eq!(rgba.len(), row_len * height as usize);
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // compression method, filter method and interlace method are all 0
+    ihdr.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in rgba.chunks_exact(row_len) {
+        encoder.write_all(&[FILTER_NONE])?;
+        encoder.write_all(row)?;
+    }
+    let idat = encoder.finish()?;
+
+    let mut png = Vec::with_capacity(SIGNATURE.len() + idat.len() + 64);
+    png.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &idat);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
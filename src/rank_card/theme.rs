@@ -0,0 +1,184 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::db::dao;
+
+/// A color without transparency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rgb(pub(crate) u8, pub(crate) u8, pub(crate) u8);
+
+/// The arrangement of the elements of the rank card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout {
+    /// The avatar on the left, the name, the exp and the progress bar on the right.
+    Wide,
+    /// The avatar at the top, everything else centered below it.
+    Stacked,
+}
+
+/// The look of the rank cards of the server.
+///
+/// The theme is stored in the single row of the `rank_card_theme` table.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Theme {
+    pub(crate) layout: Layout,
+    pub(crate) background: Rgb,
+    pub(crate) text: Rgb,
+    /// The color of the empty part of the progress bar.
+    pub(crate) bar: Rgb,
+    /// The color of the avatar placeholder. It also fills the progress bar
+    /// when the earned role of the member has no color.
+    pub(crate) accent: Rgb,
+}
+
+/// A change of one of the properties of the [`Theme`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ThemeSetting {
+    Layout(Layout),
+    Background(Rgb),
+    Text(Rgb),
+    Bar(Rgb),
+    Accent(Rgb),
+}
+
+impl Rgb {
+    pub(crate) fn from_u32(rgb: u32) -> Self {
+        let [_, r, g, b] = rgb.to_be_bytes();
+        Rgb(r, g, b)
+    }
+
+    pub(crate) fn to_u32(self) -> u32 {
+        let Rgb(r, g, b) = self;
+        u32::from_be_bytes([0, r, g, b])
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = ();
+
+    /// Parses the color written as `#RRGGBB` or `RRGGBB`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 {
+            return Err(());
+        }
+        u32::from_str_radix(hex, 16)
+            .map(Rgb::from_u32)
+            .map_err(|_| ())
+    }
+}
+
+impl Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Rgb(r, g, b) = self;
+        write!(f, "#{r:02X}{g:02X}{b:02X}")
+    }
+}
+
+impl Layout {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Layout::Wide => "wide",
+            Layout::Stacked => "stacked",
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wide" => Ok(Layout::Wide),
+            "stacked" => Ok(Layout::Stacked),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ThemeSetting {
+    pub(crate) const NAMES: &'static [&'static str] =
+        &["layout", "background", "text", "bar", "accent"];
+
+    /// Parses the setting from its name and the value given by a command.
+    pub(crate) fn parse(name: &str, value: &str) -> Result<Self, String> {
+        let colour = || {
+            value
+                .parse::<Rgb>()
+                .map_err(|()| "The color must be written as `#RRGGBB`.".to_string())
+        };
+        match name {
+            "layout" => value
+                .parse::<Layout>()
+                .map(ThemeSetting::Layout)
+                .map_err(|()| "The layout must be either `wide` or `stacked`.".to_string()),
+            "background" => colour().map(ThemeSetting::Background),
+            "text" => colour().map(ThemeSetting::Text),
+            "bar" => colour().map(ThemeSetting::Bar),
+            "accent" => colour().map(ThemeSetting::Accent),
+            _ => Err(format!(
+                "Unknown setting. Try one of: `{}`.",
+                Self::NAMES.join("`, `")
+            )),
+        }
+    }
+}
+
+impl Display for ThemeSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeSetting::Layout(layout) => write!(f, "`layout`: {}", layout.as_str()),
+            ThemeSetting::Background(rgb) => write!(f, "`background`: {rgb}"),
+            ThemeSetting::Text(rgb) => write!(f, "`text`: {rgb}"),
+            ThemeSetting::Bar(rgb) => write!(f, "`bar`: {rgb}"),
+            ThemeSetting::Accent(rgb) => write!(f, "`accent`: {rgb}"),
+        }
+    }
+}
+
+impl Theme {
+    pub(crate) fn apply(&mut self, setting: ThemeSetting) {
+        match setting {
+            ThemeSetting::Layout(layout) => self.layout = layout,
+            ThemeSetting::Background(rgb) => self.background = rgb,
+            ThemeSetting::Text(rgb) => self.text = rgb,
+            ThemeSetting::Bar(rgb) => self.bar = rgb,
+            ThemeSetting::Accent(rgb) => self.accent = rgb,
+        }
+    }
+
+    /// Returns the current values of all properties.
+    pub(crate) fn to_settings(self) -> Vec<ThemeSetting> {
+        vec![
+            ThemeSetting::Layout(self.layout),
+            ThemeSetting::Background(self.background),
+            ThemeSetting::Text(self.text),
+            ThemeSetting::Bar(self.bar),
+            ThemeSetting::Accent(self.accent),
+        ]
+    }
+}
+
+impl From<dao::RankCardTheme> for Theme {
+    fn from(dao: dao::RankCardTheme) -> Self {
+        let dao::RankCardTheme {
+            layout,
+            background,
+            text,
+            bar,
+            accent,
+        } = dao;
+        // The database constraints guarantee that the values are valid
+        #[allow(clippy::cast_sign_loss)]
+        let rgb = |colour: i32| Rgb::from_u32(colour as u32);
+        Self {
+            layout: layout.parse().unwrap_or(Layout::Wide),
+            background: rgb(background),
+            text: rgb(text),
+            bar: rgb(bar),
+            accent: rgb(accent),
+        }
+    }
+}
@@ -2,7 +2,7 @@ use rand::seq::SliceRandom;
 use serenity::{
     framework::StandardFramework,
     http::{CacheHttp, Http},
-    model::prelude::{ChannelId, GuildId, Member, UserId},
+    model::prelude::{ChannelId, GuildId, Member, RoleId, UserId},
     prelude::{EventHandler, Mentionable, TypeMap},
     utils::MessageBuilder,
     Client,
@@ -24,6 +24,9 @@ pub(crate) enum Error {
     Sqlx(#[from] sqlx::Error),
     #[error("Serenity error: {0}")]
     Serenity(#[from] serenity::Error),
+    /// Two earned roles can't need the same amount of exp.
+    #[error("The earned role {role_id} already needs {exp_needed} exp")]
+    TakenExpThreshold { role_id: RoleId, exp_needed: u64 },
 }
 
 pub(crate) type Result<T> = core::result::Result<T, Error>;